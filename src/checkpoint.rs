@@ -0,0 +1,185 @@
+use std::{
+    convert::TryInto,
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+use ufo_core::{UfoObjectParams, UfoPopulateError};
+
+use crate::{UfoCore, UfoErr, UfoHandle, COPY_CHUNK_BYTES};
+
+const MAGIC: &[u8; 8] = b"UFOSNAP1";
+// magic, then header_size, stride, element_ct and min_load_ct (0 for none) as little endian u64s,
+// then read_only as a u64 so the header bytes that follow stay aligned
+const PREAMBLE_LEN: usize = 8 + 5 * 8;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl UfoHandle {
+    /// Writes the header and the whole body to `path` for `UfoCore::restore_ufo`
+    ///
+    /// Every element is materialized on the way, a chunk at a time, so chunks already written
+    /// out can be evicted. Writes made to the object while this runs may or may not be included.
+    pub fn snapshot_to_file<P>(&self, path: P) -> Result<(), UfoErr>
+    where
+        P: AsRef<Path>,
+    {
+        self.alive()?;
+        let info = self.info;
+        let mut out = File::create(path)?;
+
+        let mut preamble = Vec::with_capacity(PREAMBLE_LEN);
+        preamble.extend_from_slice(MAGIC);
+        for field in [
+            info.header_size,
+            info.stride,
+            info.element_ct,
+            info.min_load_ct.unwrap_or(0),
+            info.read_only as usize,
+        ] {
+            preamble.extend_from_slice(&(field as u64).to_le_bytes());
+        }
+        out.write_all(&preamble)?;
+
+        let (slot, header) = self.locked_header()?;
+        out.write_all(unsafe { std::slice::from_raw_parts(header, info.header_size) })?;
+        drop(slot);
+        for chunk in self.as_bytes()?.chunks(COPY_CHUNK_BYTES) {
+            out.write_all(chunk)?;
+        }
+        out.sync_all()?;
+        Ok(())
+    }
+}
+
+impl UfoCore {
+    /// Reconstitutes an object saved with `UfoHandle::snapshot_to_file`
+    ///
+    /// The body is read back from the file lazily on first access, so the file has to stay in
+    /// place for as long as the object is alive. The original populate function is not needed,
+    /// the file holds every element.
+    pub fn restore_ufo<P>(&self, path: P) -> Result<UfoHandle, UfoErr>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        let mut preamble = [0; PREAMBLE_LEN];
+        file.read_exact(&mut preamble)?;
+        if &preamble[..8] != MAGIC {
+            return Err(invalid("not a ufo snapshot").into());
+        }
+        let field = |i: usize| {
+            let start = 8 + i * 8;
+            u64::from_le_bytes(preamble[start..start + 8].try_into().unwrap()) as usize
+        };
+        let (header_size, stride, element_ct) = (field(0), field(1), field(2));
+        let min_load_ct = Some(field(3)).filter(|&ct| ct > 0);
+        let read_only = field(4) != 0;
+
+        if stride == 0 {
+            return Err(invalid("ufo snapshot has a zero stride").into());
+        }
+        // the sizes come from the file, so they may be garbage
+        let (body_offset, expected) = PREAMBLE_LEN
+            .checked_add(header_size)
+            .and_then(|offset| Some((offset, stride.checked_mul(element_ct)?.checked_add(offset)?)))
+            .ok_or_else(|| invalid("ufo snapshot sizes overflow"))?;
+        if file.metadata()?.len() != expected as u64 {
+            return Err(invalid("ufo snapshot is truncated or has trailing data").into());
+        }
+        let mut header = vec![0; header_size];
+        file.read_exact(&mut header)?;
+
+        let params = UfoObjectParams {
+            header_size,
+            stride,
+            min_load_ct,
+            read_only,
+            element_ct,
+            populate: Box::new(move |start, end, fill| {
+                let fill = unsafe { std::slice::from_raw_parts_mut(fill, (end - start) * stride) };
+                file.read_exact_at(fill, (body_offset + start * stride) as u64)
+                    .map_err(|_| UfoPopulateError)
+            }),
+            writeback_listener: None,
+        };
+        let ufo = self.new_ufo(params)?;
+        let dst = ufo.header_ptr()?.cast::<u8>();
+        unsafe { std::ptr::copy_nonoverlapping(header.as_ptr(), dst, header_size) };
+        Ok(ufo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+    use std::mem::size_of;
+
+    #[test]
+    fn snapshot_round_trip() -> Result<(), UfoErr> {
+        let core = test_core();
+        let mut o = core.new_ufo(UfoObjectParams {
+            header_size: 8,
+            stride: size_of::<u32>(),
+            min_load_ct: Some(4096),
+            read_only: false,
+            element_ct: 100 * 1000,
+            populate: Box::new(|start, end, fill| {
+                for idx in start..end {
+                    unsafe { fill.cast::<u32>().add(idx - start).write(idx as u32) };
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        })?;
+        o.as_mut_slice::<u32>()?[5] = 0;
+        unsafe { o.header_ptr()?.cast::<u64>().write(42) };
+
+        let path = std::env::temp_dir().join(format!("ufo-{}-snapshot", std::process::id()));
+        o.snapshot_to_file(&path)?;
+        o.free()?;
+
+        let restored = core.restore_ufo(&path)?;
+        assert_eq!(Some(4096), restored.info().min_load_ct);
+        assert_eq!(42, unsafe { *restored.header_ptr()?.cast::<u64>() });
+        let arr = restored.as_slice::<u32>()?;
+        assert_eq!(0, arr[5]);
+        assert_eq!(99999, arr[99999]);
+        drop(arr);
+
+        restored.free()?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_sizes_are_refused() -> Result<(), UfoErr> {
+        let core = test_core();
+        let path = std::env::temp_dir().join(format!("ufo-{}-corrupt", std::process::id()));
+        let write = |fields: [u64; 5]| -> io::Result<()> {
+            let mut out = File::create(&path)?;
+            out.write_all(MAGIC)?;
+            for field in fields {
+                out.write_all(&field.to_le_bytes())?;
+            }
+            Ok(())
+        };
+
+        // stride times element_ct overflows
+        write([0, 1 << 40, 1 << 40, 0, 0])?;
+        assert!(matches!(core.restore_ufo(&path), Err(UfoErr::Io(_))));
+        write([0, 0, 10, 0, 0])?;
+        assert!(matches!(core.restore_ufo(&path), Err(UfoErr::Io(_))));
+        // sound sizes, but no body behind them
+        write([0, 4, 10, 0, 0])?;
+        assert!(matches!(core.restore_ufo(&path), Err(UfoErr::Io(_))));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
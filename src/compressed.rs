@@ -0,0 +1,187 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::Mutex,
+};
+
+use ufo_core::{UfoObjectParams, UfoPopulateError};
+
+use crate::PopulateCache;
+
+/// Where one compressed block sits in an archive and how many elements it decompresses to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedBlock {
+    pub offset: u64,
+    pub compressed_len: usize,
+    pub element_ct: usize,
+}
+
+/// Params for an object viewing a compressed archive, blocks are decompressed on fault
+///
+/// `index` lists the blocks in element order. A decompressed block may serve several populate
+/// calls, so up to `cache_bytes` of decompressed blocks are kept around. The object is read
+/// only, override the returned params to change that or to set `min_load_ct`. A zero `stride`
+/// is refused with `InvalidInput`.
+pub fn from_compressed_blocks<R, D>(
+    archive: R,
+    index: Vec<CompressedBlock>,
+    stride: usize,
+    decompress: D,
+    cache_bytes: usize,
+) -> io::Result<UfoObjectParams>
+where
+    R: Read + Seek + Send + 'static,
+    D: Fn(&[u8]) -> io::Result<Vec<u8>> + Send + Sync + 'static,
+{
+    if stride == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "stride must not be zero",
+        ));
+    }
+    // first element of each block
+    let starts: Vec<usize> = index
+        .iter()
+        .scan(0, |next, block| {
+            let start = *next;
+            *next += block.element_ct;
+            Some(start)
+        })
+        .collect();
+    let element_ct = index.iter().map(|block| block.element_ct).sum();
+    let archive = Mutex::new(archive);
+    let cache = PopulateCache::new(cache_bytes);
+
+    let load = move |block: usize| -> io::Result<Vec<u8>> {
+        let entry = index[block];
+        let mut compressed = vec![0; entry.compressed_len];
+        {
+            let mut archive = archive
+                .lock()
+                .map_err(|_| io::Error::other("archive reader poisoned"))?;
+            archive.seek(SeekFrom::Start(entry.offset))?;
+            archive.read_exact(&mut compressed)?;
+        }
+        let data = decompress(&compressed)?;
+        if data.len() != entry.element_ct * stride {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "block {} decompressed to {} bytes, expected {}",
+                    block,
+                    data.len(),
+                    entry.element_ct * stride
+                ),
+            ));
+        }
+        Ok(data)
+    };
+
+    Ok(UfoObjectParams {
+        header_size: 0,
+        stride,
+        min_load_ct: None,
+        read_only: true,
+        element_ct,
+        populate: Box::new(move |start, end, fill| {
+            let mut idx = start;
+            while idx < end {
+                let block = starts.partition_point(|s| *s <= idx) - 1;
+                let block_start = starts[block];
+                let data = cache
+                    .try_get_or_insert_with(block, || load(block))
+                    .map_err(|_| UfoPopulateError)?;
+
+                let block_end = (block_start + data.len() / stride).min(end);
+                let from = (idx - block_start) * stride;
+                let len = (block_end - idx) * stride;
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        data[from..from + len].as_ptr(),
+                        fill.add((idx - start) * stride),
+                        len,
+                    )
+                };
+                idx = block_end;
+            }
+            Ok(())
+        }),
+        writeback_listener: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+    use crate::UfoErr;
+    use std::{
+        io::Cursor,
+        mem::size_of,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    // stand in for a real codec, flips every bit
+    fn squash(data: &[u8]) -> Vec<u8> {
+        data.iter().map(|b| !b).collect()
+    }
+
+    #[test]
+    fn reads_through_blocks() -> Result<(), UfoErr> {
+        let core = test_core();
+
+        // blocks of uneven sizes, none of them page aligned
+        let mut archive = Vec::new();
+        let mut index = Vec::new();
+        let mut next = 0u32;
+        for element_ct in [1000, 3333, 17, 5000, 2048] {
+            let raw: Vec<u8> = (next..next + element_ct as u32)
+                .flat_map(|i| i.to_ne_bytes())
+                .collect();
+            let compressed = squash(&raw);
+            index.push(CompressedBlock {
+                offset: archive.len() as u64,
+                compressed_len: compressed.len(),
+                element_ct,
+            });
+            archive.extend(compressed);
+            next += element_ct as u32;
+        }
+
+        let decompressed = Arc::new(AtomicUsize::new(0));
+        let count = decompressed.clone();
+        let params = from_compressed_blocks(
+            Cursor::new(archive),
+            index,
+            size_of::<u32>(),
+            move |data| {
+                count.fetch_add(1, Ordering::Relaxed);
+                Ok(squash(data))
+            },
+            1024 * 1024,
+        )?;
+        assert_eq!(next as usize, params.element_ct);
+
+        let o = core.new_ufo(params)?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), next as usize) };
+        for (i, x) in arr.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+        assert_eq!(5, decompressed.load(Ordering::Relaxed));
+        Ok(())
+    }
+
+    #[test]
+    fn zero_stride_is_refused() {
+        let params = from_compressed_blocks(
+            Cursor::new(Vec::new()),
+            Vec::new(),
+            0,
+            |d| Ok(d.to_vec()),
+            0,
+        );
+        assert!(matches!(params, Err(e) if e.kind() == io::ErrorKind::InvalidInput));
+    }
+}
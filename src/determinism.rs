@@ -0,0 +1,83 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+    ops::Range,
+    sync::Mutex,
+};
+
+/// Checksums of every range an object's populate function produced, to catch populate
+/// functions that return different data for the same range
+#[derive(Default)]
+pub(crate) struct PopulateChecksums {
+    // start index to end index and checksum of the filled bytes
+    seen: Mutex<HashMap<usize, (usize, u64)>>,
+    // ranges that came back different, kept across resets since they point at a bug
+    mismatches: Mutex<Vec<Range<usize>>>,
+}
+
+impl PopulateChecksums {
+    /// Records `[start, end)` as a mismatch if it was populated before with different contents
+    ///
+    /// This runs on the core's fault thread, so it must not panic: that would leave the
+    /// faulting thread waiting forever.
+    pub(crate) fn check(&self, start: usize, end: usize, filled: &[u8]) {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(filled);
+        let sum = hasher.finish();
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        match seen.insert(start, (end, sum)) {
+            Some((prev_end, prev_sum)) if prev_end == end && prev_sum != sum => {
+                #[cfg(feature = "tracing")]
+                tracing::error!(start, end, "populate is not deterministic, range changed");
+                let mut mismatches = self.mismatches.lock().unwrap_or_else(|e| e.into_inner());
+                mismatches.push(start..end);
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn mismatches(&self) -> Vec<Range<usize>> {
+        self.mismatches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Forgets every checksum, for when the object's contents are legitimately regenerated
+    pub(crate) fn clear(&self) {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_populate_matches() {
+        let sums = PopulateChecksums::default();
+        sums.check(0, 4, &[1, 2, 3, 4]);
+        sums.check(0, 4, &[1, 2, 3, 4]);
+        // a different chunking of the same start is not comparable
+        sums.check(0, 2, &[9, 9]);
+        assert!(sums.mismatches().is_empty());
+    }
+
+    #[test]
+    fn changed_populate_is_recorded() {
+        let sums = PopulateChecksums::default();
+        sums.check(0, 4, &[1, 2, 3, 4]);
+        sums.check(0, 4, &[1, 2, 3, 5]);
+        assert_eq!(vec![0..4], sums.mismatches());
+    }
+
+    #[test]
+    fn clear_forgets_ranges() {
+        let sums = PopulateChecksums::default();
+        sums.check(0, 4, &[1, 2, 3, 4]);
+        sums.clear();
+        sums.check(0, 4, &[4, 3, 2, 1]);
+        assert!(sums.mismatches().is_empty());
+    }
+}
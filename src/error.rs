@@ -0,0 +1,204 @@
+use std::{fmt, sync::PoisonError};
+
+use ufo_core::{UfoAllocateErr, UfoInternalErr};
+
+use crate::WaitTimeout;
+
+/// Errors from operations implemented in this crate on top of ufo_core
+#[derive(Debug)]
+pub enum UfoErr {
+    /// The core itself reported a failure
+    Core(UfoInternalErr),
+    /// A system call made on the object's memory failed
+    Io(std::io::Error),
+    /// Two objects used together have different strides
+    StrideMismatch { src: usize, dst: usize },
+    /// The object was sealed and can no longer be written
+    Sealed,
+    /// The object was allocated read only, writes to it would be lost on eviction
+    ReadOnly,
+    /// A typed view was requested for elements of a different size than the object's stride
+    ElementSize { stride: usize, element_size: usize },
+    /// The operation needs an object allocated read only
+    Writable,
+    /// The object's populate function failed under `PopulateFailurePolicy::Poison`
+    Poisoned,
+    /// The operation is still running, the waiter inside can be used to keep waiting
+    Timeout(WaitTimeout<()>),
+    /// The core still has this many live objects
+    HandlesOutstanding(usize),
+    /// `UfoCore::self_test` read back something other than what it expected
+    SelfTestFailed(&'static str),
+    /// The object is held by a view such as `UfoSlice` and cannot be freed until it is dropped
+    InUse,
+    /// `count` elements from `start` run past the end of an object of `len` elements
+    OutOfRange { start: usize, count: usize, len: usize },
+}
+
+impl fmt::Display for UfoErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UfoErr::Core(e) => write!(f, "ufo core error: {:?}", e),
+            UfoErr::Io(e) => write!(f, "io error: {}", e),
+            UfoErr::StrideMismatch { src, dst } => {
+                write!(f, "stride mismatch: source {} bytes, destination {} bytes", src, dst)
+            }
+            UfoErr::Sealed => write!(f, "object is sealed"),
+            UfoErr::ReadOnly => write!(f, "object is read only"),
+            UfoErr::ElementSize {
+                stride,
+                element_size,
+            } => write!(
+                f,
+                "element size {} does not match the object's stride {}",
+                element_size, stride
+            ),
+            UfoErr::Writable => write!(f, "object is not read only"),
+            UfoErr::Poisoned => write!(f, "object is poisoned by a failed populate"),
+            UfoErr::Timeout(e) => write!(f, "{}", e),
+            UfoErr::HandlesOutstanding(ct) => write!(f, "{} ufos are still alive", ct),
+            UfoErr::SelfTestFailed(step) => write!(f, "self test failed: {}", step),
+            UfoErr::InUse => write!(f, "object is in use"),
+            UfoErr::OutOfRange { start, count, len } => write!(
+                f,
+                "{} elements from {} are out of range for {} elements",
+                count, start, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UfoErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UfoErr::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Broad reason a core failed to start, each with its usual fix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupFailure {
+    /// The process may not create a userfaultfd
+    PermissionDenied,
+    /// The kernel lacks userfaultfd or a feature the core needs
+    Unsupported,
+    /// Out of file descriptors or memory
+    ResourceExhausted,
+    Other,
+}
+
+/// Error returned by `UfoCore::new_ufo_core`
+#[derive(Debug)]
+pub struct UfoStartErr {
+    kind: StartupFailure,
+    source: std::io::Error,
+}
+
+impl UfoStartErr {
+    pub fn kind(&self) -> StartupFailure {
+        self.kind
+    }
+
+    /// What usually fixes this kind of failure, if there is a common fix
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self.kind {
+            StartupFailure::PermissionDenied => Some(
+                "run with CAP_SYS_PTRACE or set the vm.unprivileged_userfaultfd sysctl to 1",
+            ),
+            StartupFailure::Unsupported => {
+                Some("userfaultfd needs a kernel built with CONFIG_USERFAULTFD, 4.11 or newer")
+            }
+            StartupFailure::ResourceExhausted => {
+                Some("raise the open file limit (ulimit -n) or free memory")
+            }
+            StartupFailure::Other => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for UfoStartErr {
+    fn from(source: std::io::Error) -> Self {
+        let kind = match source.raw_os_error() {
+            Some(libc::EPERM) | Some(libc::EACCES) => StartupFailure::PermissionDenied,
+            Some(libc::ENOSYS) | Some(libc::EINVAL) | Some(libc::EOPNOTSUPP) => {
+                StartupFailure::Unsupported
+            }
+            Some(libc::EMFILE) | Some(libc::ENFILE) | Some(libc::ENOMEM) => {
+                StartupFailure::ResourceExhausted
+            }
+            _ => StartupFailure::Other,
+        };
+        UfoStartErr { kind, source }
+    }
+}
+
+impl From<UfoStartErr> for std::io::Error {
+    fn from(e: UfoStartErr) -> Self {
+        e.source
+    }
+}
+
+impl fmt::Display for UfoStartErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ufo core failed to start: {}", self.source)?;
+        if let Some(fix) = self.remediation() {
+            write!(f, " ({})", fix)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UfoStartErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<UfoInternalErr> for UfoErr {
+    fn from(e: UfoInternalErr) -> Self {
+        UfoErr::Core(e)
+    }
+}
+
+impl From<UfoAllocateErr> for UfoErr {
+    fn from(e: UfoAllocateErr) -> Self {
+        UfoErr::Core(e.into())
+    }
+}
+
+impl From<std::io::Error> for UfoErr {
+    fn from(e: std::io::Error) -> Self {
+        UfoErr::Io(e)
+    }
+}
+
+impl<T> From<PoisonError<T>> for UfoErr {
+    fn from(e: PoisonError<T>) -> Self {
+        UfoErr::Core(e.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn classifies_startup_failures() {
+        let denied = UfoStartErr::from(io::Error::from_raw_os_error(libc::EPERM));
+        assert_eq!(StartupFailure::PermissionDenied, denied.kind());
+        assert!(denied.to_string().contains("vm.unprivileged_userfaultfd"));
+
+        let old_kernel = UfoStartErr::from(io::Error::from_raw_os_error(libc::ENOSYS));
+        assert_eq!(StartupFailure::Unsupported, old_kernel.kind());
+
+        let no_fds = UfoStartErr::from(io::Error::from_raw_os_error(libc::EMFILE));
+        assert_eq!(StartupFailure::ResourceExhausted, no_fds.kind());
+
+        let other = UfoStartErr::from(io::Error::other("callback"));
+        assert_eq!(StartupFailure::Other, other.kind());
+        assert_eq!(None, other.remediation());
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+};
+
+use crossbeam::channel::{self, Receiver, Sender, TrySendError};
+use ufo_core::{UfoEvent, UfoEventConsumer};
+
+/// Number of events buffered per receiver before the oldest ones are dropped
+pub const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// Pull side of `UfoCore::event_receiver`, derefs to the underlying channel receiver
+///
+/// Clones share one queue, the queue is unsubscribed once every clone has been dropped.
+#[derive(Clone)]
+pub struct EventReceiver<E = UfoEvent> {
+    rx: Receiver<E>,
+    _subscribed: Arc<()>,
+}
+
+impl<E> Deref for EventReceiver<E> {
+    type Target = Receiver<E>;
+
+    fn deref(&self) -> &Receiver<E> {
+        &self.rx
+    }
+}
+
+// The hub keeps its own receiver for each queue so it can make room by discarding the oldest
+// event, the weak token tells it when the user's side is gone
+struct Queue<E> {
+    tx: Sender<E>,
+    rx: Receiver<E>,
+    subscribed: Weak<()>,
+}
+
+// One bounded queue per receiver
+struct EventQueues<E> {
+    queues: Mutex<Vec<Queue<E>>>,
+    dropped_events: AtomicU64,
+}
+
+impl<E: Clone> EventQueues<E> {
+    fn new() -> Self {
+        EventQueues {
+            queues: Mutex::new(Vec::new()),
+            dropped_events: AtomicU64::new(0),
+        }
+    }
+
+    fn receiver(&self) -> EventReceiver<E> {
+        let (tx, rx) = channel::bounded(EVENT_QUEUE_CAPACITY);
+        let subscribed = Arc::new(());
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues.push(Queue {
+            tx,
+            rx: rx.clone(),
+            subscribed: Arc::downgrade(&subscribed),
+        });
+        EventReceiver {
+            rx,
+            _subscribed: subscribed,
+        }
+    }
+
+    fn push(&self, event: &E) {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues.retain(|Queue { tx, rx, subscribed }| {
+            if subscribed.strong_count() == 0 {
+                return false;
+            }
+            let mut event = event.clone();
+            loop {
+                match tx.try_send(event) {
+                    Ok(()) => return true,
+                    // the consumer may drain the queue in between, then nothing needs dropping
+                    Err(TrySendError::Full(e)) => {
+                        if rx.try_recv().is_ok() {
+                            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+                        }
+                        event = e;
+                    }
+                    Err(TrySendError::Disconnected(_)) => return false,
+                }
+            }
+        });
+    }
+}
+
+/// Fans the core's single event callback out to the user callback and any pull receivers
+pub(crate) struct EventHub {
+    callback: RwLock<Option<Arc<UfoEventConsumer>>>,
+    queues: EventQueues<UfoEvent>,
+}
+
+impl EventHub {
+    pub(crate) fn new() -> Self {
+        EventHub {
+            callback: RwLock::new(None),
+            queues: EventQueues::new(),
+        }
+    }
+
+    pub(crate) fn set_callback(&self, callback: Option<Box<UfoEventConsumer>>) {
+        // a poisoned lock only means a previous callback panicked, replacing it is still fine
+        let mut current = self.callback.write().unwrap_or_else(|e| e.into_inner());
+        *current = callback.map(Arc::from);
+    }
+
+    pub(crate) fn receiver(&self) -> EventReceiver {
+        self.queues.receiver()
+    }
+
+    pub(crate) fn dropped_events(&self) -> u64 {
+        self.queues.dropped_events.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn dispatch(&self, event: &UfoEvent) {
+        // never panic or block here, this runs on the core's threads. The callback runs with no
+        // lock held so it can replace itself or subscribe
+        let callback = match self.callback.read() {
+            Ok(callback) => callback.clone(),
+            Err(_) => None,
+        };
+        if let Some(callback) = callback {
+            callback(event);
+        }
+        self.queues.push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc, Weak};
+
+    use ufo_core::UfoEvent;
+
+    use super::{EventHub, EventQueues, EVENT_QUEUE_CAPACITY};
+    use crate::{
+        test_util::{sequence_params, test_core},
+        UfoErr,
+    };
+
+    // Any event the core emits, the hub does not look inside them
+    fn some_event() -> Result<UfoEvent, UfoErr> {
+        let core = test_core();
+        let rx = core.event_receiver();
+        core.new_ufo(sequence_params(1000))?.free()?;
+        drop(core);
+        Ok(rx.try_recv().expect("the core emitted no events"))
+    }
+
+    #[test]
+    fn dropped_receivers_are_pruned() -> Result<(), UfoErr> {
+        let event = some_event()?;
+        let hub = EventHub::new();
+        let kept = hub.receiver();
+        drop(hub.receiver());
+        let cloned = hub.receiver();
+        drop(cloned.clone());
+
+        for _ in 0..EVENT_QUEUE_CAPACITY + 1 {
+            hub.dispatch(&event);
+        }
+        assert_eq!(2, hub.queues.queues.lock().unwrap().len());
+        assert_eq!(EVENT_QUEUE_CAPACITY, kept.try_iter().count());
+        assert_eq!(2, hub.dropped_events());
+        Ok(())
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest_events() {
+        let queues = EventQueues::new();
+        let rx = queues.receiver();
+        for event in 0..EVENT_QUEUE_CAPACITY + 10 {
+            queues.push(&event);
+        }
+
+        let kept: Vec<usize> = rx.try_iter().collect();
+        assert_eq!((10..EVENT_QUEUE_CAPACITY + 10).collect::<Vec<_>>(), kept);
+        assert_eq!(10, queues.dropped_events.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn callback_can_touch_the_hub() -> Result<(), UfoErr> {
+        let event = some_event()?;
+        let hub = Arc::new(EventHub::new());
+        let weak: Weak<EventHub> = Arc::downgrade(&hub);
+        hub.set_callback(Some(Box::new(move |_| {
+            if let Some(hub) = weak.upgrade() {
+                drop(hub.receiver());
+                hub.set_callback(None);
+            }
+        })));
+
+        hub.dispatch(&event);
+        assert!(hub.callback.read().unwrap().is_none());
+        Ok(())
+    }
+}
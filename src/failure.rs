@@ -0,0 +1,121 @@
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+/// What happens to an object when its populate function returns an error
+///
+/// Either way the range that failed is filled with zeros so the thread that faulted on it
+/// can continue, and the object reports `is_poisoned`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PopulateFailurePolicy {
+    /// Failed ranges read as zeros, `reset` clears the poison so the ranges can be retried
+    #[default]
+    ZeroFill,
+    /// The object is dead until freed, operations on it fail with `UfoErr::Poisoned`
+    Poison,
+}
+
+pub(crate) struct PoisonState {
+    policy: PopulateFailurePolicy,
+    poisoned: AtomicBool,
+    // element ranges whose populate failed, sorted with overlapping and adjacent ranges merged
+    failed: Mutex<Vec<Range<usize>>>,
+}
+
+impl PoisonState {
+    pub(crate) fn new(policy: PopulateFailurePolicy) -> Self {
+        PoisonState {
+            policy,
+            poisoned: AtomicBool::new(false),
+            failed: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> PopulateFailurePolicy {
+        self.policy
+    }
+
+    fn failed(&self) -> MutexGuard<'_, Vec<Range<usize>>> {
+        // the list is consistent between statements, so a panic elsewhere does not corrupt it
+        self.failed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Called by the populate wrapper before the faulting thread is released
+    pub(crate) fn poison(&self, range: Range<usize>) {
+        {
+            let mut failed = self.failed();
+            failed.push(range);
+            failed.sort_by_key(|r| r.start);
+            let mut merged: Vec<Range<usize>> = Vec::with_capacity(failed.len());
+            for r in failed.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                    _ => merged.push(r),
+                }
+            }
+            *failed = merged;
+        }
+        self.poisoned.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn failed_ranges(&self) -> Vec<Range<usize>> {
+        self.failed().clone()
+    }
+
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// The object's contents were discarded, failures under `ZeroFill` no longer apply
+    pub(crate) fn on_reset(&self) {
+        if self.policy == PopulateFailurePolicy::ZeroFill {
+            self.failed().clear();
+            self.poisoned.store(false, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn is_dead(&self) -> bool {
+        self.policy == PopulateFailurePolicy::Poison && self.is_poisoned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_ranges_merge() {
+        let state = PoisonState::new(PopulateFailurePolicy::ZeroFill);
+        state.poison(8..16);
+        state.poison(0..8);
+        state.poison(32..40);
+
+        assert_eq!(vec![0..16, 32..40], state.failed_ranges());
+        state.on_reset();
+        assert!(state.failed_ranges().is_empty());
+    }
+
+    #[test]
+    fn bridging_range_joins_both_sides() {
+        let state = PoisonState::new(PopulateFailurePolicy::ZeroFill);
+        state.poison(0..8);
+        state.poison(16..24);
+        state.poison(8..16);
+
+        assert_eq!(vec![0..24], state.failed_ranges());
+    }
+
+    #[test]
+    fn repoisoning_is_not_duplicated() {
+        let state = PoisonState::new(PopulateFailurePolicy::ZeroFill);
+        state.poison(8..16);
+        state.poison(8..16);
+        state.poison(10..12);
+
+        assert_eq!(vec![8..16], state.failed_ranges());
+    }
+}
@@ -0,0 +1,154 @@
+use std::{fs::File, io, os::unix::fs::FileExt, path::Path};
+
+use ufo_core::{UfoObjectParams, UfoPopulateError};
+
+use crate::{UfoCore, UfoErr, UfoHandle};
+
+// Opens the file and counts its records, refusing files that end in a partial record
+fn open_records(path: &Path, record_size: usize) -> io::Result<(File, usize)> {
+    if record_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "records must not be empty",
+        ));
+    }
+    let file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    if len % record_size != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is {} bytes, not a whole number of {} byte records",
+                path.display(),
+                len,
+                record_size
+            ),
+        ));
+    }
+    Ok((file, len / record_size))
+}
+
+impl UfoCore {
+    /// Allocates an object over the file at `path`, one element per `stride` bytes
+    ///
+    /// Elements are read from the file on first access. Writes stay in memory and go through the
+    /// normal writeback path, the file itself is never modified. A read error is a populate
+    /// failure and zero-fills the range.
+    pub fn new_ufo_from_file<P>(&self, path: P, stride: usize) -> Result<UfoHandle, UfoErr>
+    where
+        P: AsRef<Path>,
+    {
+        let (file, element_ct) = open_records(path.as_ref(), stride)?;
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride,
+            min_load_ct: None,
+            read_only: false,
+            element_ct,
+            populate: Box::new(move |start, end, fill| {
+                let fill = unsafe { std::slice::from_raw_parts_mut(fill, (end - start) * stride) };
+                file.read_exact_at(fill, (start * stride) as u64)
+                    .map_err(|_| UfoPopulateError)
+            }),
+            writeback_listener: None,
+        };
+        Ok(self.new_ufo(params)?)
+    }
+
+    /// Like `new_ufo_from_file` for files of `record_size` byte records that need decoding
+    ///
+    /// `decode(start, records, elements)` is called once per populated chunk with the raw
+    /// records of elements `start..start + n` and the `n * stride` bytes to decode them into.
+    pub fn new_ufo_from_file_with<P, F>(
+        &self,
+        path: P,
+        record_size: usize,
+        stride: usize,
+        decode: F,
+    ) -> Result<UfoHandle, UfoErr>
+    where
+        P: AsRef<Path>,
+        F: Fn(usize, &[u8], &mut [u8]) + Send + Sync + 'static,
+    {
+        let (file, element_ct) = open_records(path.as_ref(), record_size)?;
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride,
+            min_load_ct: None,
+            read_only: false,
+            element_ct,
+            populate: Box::new(move |start, end, fill| {
+                let mut records = vec![0; (end - start) * record_size];
+                file.read_exact_at(&mut records, (start * record_size) as u64)
+                    .map_err(|_| UfoPopulateError)?;
+                let fill = unsafe { std::slice::from_raw_parts_mut(fill, (end - start) * stride) };
+                decode(start, &records, fill);
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+        Ok(self.new_ufo(params)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+    use std::{convert::TryInto, path::PathBuf};
+
+    fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ufo-{}-{}", std::process::id(), name));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn file_backed() -> Result<(), UfoErr> {
+        let core = test_core();
+        let data: Vec<u8> = (0..100 * 1000u32).flat_map(|i| i.to_ne_bytes()).collect();
+        let path = temp_file("raw", &data);
+
+        let mut o = core.new_ufo_from_file(&path, 4)?;
+        assert_eq!(100 * 1000, o.info().element_ct);
+        let mut arr = o.as_mut_slice::<u32>()?;
+        for (i, x) in arr.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+        arr[3] = 7;
+        drop(arr);
+
+        assert_eq!(&data[..], &std::fs::read(&path)?[..]);
+        assert!(matches!(
+            core.new_ufo_from_file(&path, 3),
+            Err(UfoErr::Io(_))
+        ));
+        assert!(matches!(
+            core.new_ufo_from_file(&path, 0),
+            Err(UfoErr::Io(e)) if e.kind() == std::io::ErrorKind::InvalidInput
+        ));
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn file_backed_with_decoder() -> Result<(), UfoErr> {
+        let core = test_core();
+        // big endian u64 records decoded into native u32 elements
+        let data: Vec<u8> = (0..10 * 1000u64).flat_map(|i| i.to_be_bytes()).collect();
+        let path = temp_file("decoded", &data);
+
+        let o = core.new_ufo_from_file_with(&path, 8, 4, |_, records, elements| {
+            for (record, element) in records.chunks(8).zip(elements.chunks_mut(4)) {
+                let x = u64::from_be_bytes(record.try_into().unwrap()) as u32;
+                element.copy_from_slice(&x.to_ne_bytes());
+            }
+        })?;
+        for (i, x) in o.as_slice::<u32>()?.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use std::mem::size_of;
+
+use ufo_core::UfoObjectParams;
+
+/// Params for an object whose elements all start out as zero bytes
+///
+/// The populate function only clears the core's buffer. Skipping populate entirely and mapping
+/// demand zero pages would need support from the core.
+pub fn zeroed_params(element_ct: usize, stride: usize) -> UfoObjectParams {
+    UfoObjectParams {
+        header_size: 0,
+        stride,
+        min_load_ct: None,
+        read_only: false,
+        element_ct,
+        populate: Box::new(move |start, end, fill| {
+            unsafe { std::ptr::write_bytes(fill, 0, (end - start) * stride) };
+            Ok(())
+        }),
+        writeback_listener: None,
+    }
+}
+
+/// Params for an object whose elements all start out as `value`
+pub fn constant_params<T>(element_ct: usize, value: T) -> UfoObjectParams
+where
+    T: Copy + Send + Sync + 'static,
+{
+    assert!(size_of::<T>() > 0, "zero sized elements are not supported");
+    UfoObjectParams {
+        header_size: 0,
+        stride: size_of::<T>(),
+        min_load_ct: None,
+        read_only: false,
+        element_ct,
+        populate: Box::new(move |start, end, fill| {
+            let fill = unsafe { std::slice::from_raw_parts_mut(fill.cast::<T>(), end - start) };
+            fill.fill(value);
+            Ok(())
+        }),
+        writeback_listener: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::test_core, UfoErr};
+
+    #[test]
+    fn zeroed_and_constant() -> Result<(), UfoErr> {
+        let core = test_core();
+
+        let zeroed = core.new_ufo(zeroed_params(100 * 1000, 3))?;
+        assert!(zeroed.as_bytes()?.iter().all(|&b| b == 0));
+
+        let constant = core.new_ufo(constant_params(100 * 1000, 1.5f64))?;
+        assert!(constant.as_slice::<f64>()?.iter().all(|&x| x == 1.5));
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    mem::size_of,
+    ops::{Index, IndexMut},
+};
+
+use bytemuck::Pod;
+use ufo_core::{UfoInternalErr, UfoObjectParams};
+
+use crate::{UfoCore, UfoHandle, UfoVec};
+
+/// A fixed length vector whose elements are generated on first access
+///
+/// Elements are produced by the generator in chunks of the core's choosing and may be
+/// regenerated after eviction, so the generator must always return the same value for an index.
+pub struct LazyVec<T> {
+    vec: UfoVec<T>,
+}
+
+impl<T> LazyVec<T>
+where
+    T: Pod,
+{
+    pub fn new<F>(core: &UfoCore, len: usize, generator: F) -> Result<Self, UfoInternalErr>
+    where
+        F: Fn(usize) -> T + Send + Sync + 'static,
+    {
+        Self::with_min_load_ct(core, len, None, generator)
+    }
+
+    pub fn with_min_load_ct<F>(
+        core: &UfoCore,
+        len: usize,
+        min_load_ct: Option<usize>,
+        generator: F,
+    ) -> Result<Self, UfoInternalErr>
+    where
+        F: Fn(usize) -> T + Send + Sync + 'static,
+    {
+        assert!(size_of::<T>() > 0, "LazyVec does not support zero sized elements");
+
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<T>(),
+            min_load_ct,
+            read_only: false,
+            element_ct: len,
+            populate: Box::new(move |start, end, fill| {
+                let fill = fill.cast::<T>();
+                for idx in start..end {
+                    unsafe { fill.add(idx - start).write(generator(idx)) };
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+
+        let vec = UfoVec::from_handle(core, core.new_ufo(params)?)?;
+        Ok(LazyVec { vec })
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        self.vec.as_slice()
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.vec.as_mut_slice()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.as_mut_slice().iter_mut()
+    }
+
+    pub(crate) fn handle(&self) -> &UfoHandle {
+        self.vec.handle()
+    }
+
+    /// Discards all writes, elements are generated again on next access
+    pub fn reset(&mut self) -> Result<(), UfoInternalErr> {
+        self.vec.reset()
+    }
+}
+
+impl<T> Index<usize> for LazyVec<T>
+where
+    T: Pod,
+{
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        &self.as_slice()[idx]
+    }
+}
+
+impl<T> IndexMut<usize> for LazyVec<T>
+where
+    T: Pod,
+{
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        &mut self.as_mut_slice()[idx]
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LazyVec<T>
+where
+    T: Pod,
+{
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+
+    #[test]
+    fn lazy_vec_generates() -> Result<(), UfoInternalErr> {
+        let core = test_core();
+        let v = LazyVec::new(&core, 1000 * 1000, |i| (i * 3) as u64)?;
+
+        assert_eq!(1000 * 1000, v.len());
+        assert_eq!(300, v[100]);
+        for (i, x) in v.iter().enumerate() {
+            assert_eq!((i * 3) as u64, *x);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_vec_writes() -> Result<(), UfoInternalErr> {
+        let core = test_core();
+        let mut v = LazyVec::with_min_load_ct(&core, 100 * 1000, Some(4096), |i| i as u32)?;
+
+        v[17] = 4;
+        assert_eq!(4, v[17]);
+        assert_eq!(18, v[18]);
+
+        v.reset()?;
+        assert_eq!(17, v[17]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_vec_outlives_core_clone() -> Result<(), UfoInternalErr> {
+        // the vec keeps the core alive even after the caller's reference is gone
+        let v = LazyVec::new(&test_core(), 1000, |i| i as u16)?;
+        assert_eq!(999, v[999]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    backtrace::Backtrace,
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::UfoInfo;
+
+/// Every object of a core that has not been freed yet, with where it was allocated
+#[derive(Default)]
+pub(crate) struct AllocationRegistry {
+    next_id: AtomicU64,
+    live: Mutex<BTreeMap<u64, (UfoInfo, Arc<Backtrace>)>>,
+}
+
+impl AllocationRegistry {
+    /// Captures the caller's backtrace and records a new live object
+    pub(crate) fn register(self: &Arc<Self>, info: UfoInfo) -> AllocationSite {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let backtrace = Arc::new(Backtrace::force_capture());
+        self.lock().insert(id, (info, backtrace.clone()));
+        AllocationSite {
+            id,
+            backtrace,
+            registry: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, (UfoInfo, Arc<Backtrace>)>> {
+        self.live.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn report(&self) -> String {
+        let live = self.lock();
+        let mut report = format!("{} live objects\n", live.len());
+        for (id, (info, backtrace)) in live.iter() {
+            let _ = writeln!(
+                report,
+                "object {}: {} elements of {} bytes, allocated at:\n{}",
+                id, info.element_ct, info.stride, backtrace
+            );
+        }
+        report
+    }
+}
+
+/// Where one object was allocated, removed from the registry once the object is freed
+pub(crate) struct AllocationSite {
+    id: u64,
+    backtrace: Arc<Backtrace>,
+    registry: Arc<AllocationRegistry>,
+}
+
+impl AllocationSite {
+    pub(crate) fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+
+    pub(crate) fn release(&self) {
+        self.registry.lock().remove(&self.id);
+    }
+}
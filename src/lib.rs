@@ -1,192 +1,2436 @@
 // #![feature(ptr_internals, once_cell, slice_ptr_get)]
 
-use std::{io::Error, sync::Arc};
+pub use bytemuck::Pod;
+use crossbeam::channel::{self, Receiver};
+use std::{
+    ffi::c_void,
+    fmt::Debug,
+    fs::File,
+    hash::Hasher,
+    io::{Error, Write},
+    mem::size_of,
+    ops::Range,
+    os::unix::io::AsRawFd,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard, TryLockError,
+    },
+    time::{Duration, Instant},
+};
+use twox_hash::XxHash64;
 use ufo_core::*;
 
-#[derive(Clone)]
-pub struct UfoCore {
-    core: Arc<ufo_core::UfoCore>,
+mod checkpoint;
+mod compressed;
+mod determinism;
+mod error;
+mod events;
+mod failure;
+mod file;
+mod fill;
+mod lazy_vec;
+#[cfg(feature = "backtrace")]
+mod leaks;
+mod matrix;
+mod populate_cache;
+mod prefetch;
+mod pending;
+mod rate_limit;
+mod shared;
+mod sharded;
+mod slice;
+mod stateful;
+mod stats;
+#[cfg(test)]
+pub(crate) mod test_util;
+#[cfg(feature = "testing")]
+mod testing;
+mod transaction;
+mod waiter;
+mod writeback;
+
+pub use compressed::{from_compressed_blocks, CompressedBlock};
+pub use error::{StartupFailure, UfoErr, UfoStartErr};
+use determinism::PopulateChecksums;
+use events::EventHub;
+use failure::PoisonState;
+pub use failure::PopulateFailurePolicy;
+pub use fill::{constant_params, zeroed_params};
+use pending::PendingFrees;
+use prefetch::{PrefetchJob, Prefetcher};
+use rate_limit::PopulatePacer;
+#[cfg(feature = "metrics")]
+pub use stats::metric_names;
+pub use stats::UfoStats;
+use stats::{PopulateCounters, WritebackCounters};
+pub use stats::POPULATE_TIME_BUCKETS;
+pub use events::{EventReceiver, EVENT_QUEUE_CAPACITY};
+pub use lazy_vec::LazyVec;
+#[cfg(feature = "backtrace")]
+use leaks::{AllocationRegistry, AllocationSite};
+pub use matrix::UfoMatrix;
+pub use populate_cache::PopulateCache;
+pub use shared::SharedUfo;
+pub use sharded::{ShardedUfo, SHARD_BLOCK};
+pub use slice::{StreamingChunks, UfoSlice, UfoSliceMut};
+pub use stateful::{populate_pooled, populate_serialized};
+#[cfg(feature = "testing")]
+pub use testing::FaultPlan;
+pub use transaction::TxnSlice;
+pub use waiter::{UfoWaiter, WaitTimeout};
+pub use writeback::{WithWritebackListener, WritebackEvent};
+
+fn system_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
 }
 
-impl std::fmt::Debug for UfoCore {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("UfoCore").field("core", &"...").finish()
+// The core rounds populate and writeback chunks up to min_load_ct, which may run past the last
+// element. Clamps `end` to the object and returns it with the chunk's size in bytes, `None` if
+// nothing of the chunk is left
+fn clamp_chunk(
+    start: usize,
+    end: usize,
+    element_ct: usize,
+    stride: usize,
+) -> Option<(usize, usize)> {
+    let end = end.min(element_ct);
+    (start < end).then(|| (end, (end - start) * stride))
+}
+
+fn protect(ptr: *mut c_void, len: usize, prot: libc::c_int) -> std::io::Result<()> {
+    let page_size = system_page_size();
+    let len = len.div_ceil(page_size) * page_size;
+    if len == 0 {
+        return Ok(());
+    }
+    if unsafe { libc::mprotect(ptr, len, prot) } != 0 {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(())
 }
 
-impl UfoCore {
-    pub fn new_ufo_core(config: UfoCoreConfig) -> Result<UfoCore, Error> {
-        let core = ufo_core::UfoCore::new(config)?;
-        Ok(UfoCore { core })
+// Flushes everything written to the filesystem holding `path`, such as the core's writeback files
+fn sync_filesystem(path: &Path) -> std::io::Result<()> {
+    let dir = File::open(path)?;
+    if unsafe { libc::syncfs(dir.as_raw_fd()) } != 0 {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(())
+}
 
-    pub fn new_ufo(&self, prototype: UfoObjectParams) -> Result<UfoHandle, UfoAllocateErr> {
-        let ufo =  Some(self.core.allocate_ufo(prototype.new_config())?);
-        Ok(UfoHandle { ufo })
+// Read only objects are never written back, so a write through the body would silently be lost
+// on eviction. The core installs pages with UFFDIO_COPY, which ignores the protection.
+fn protect_read_only(info: &UfoInfo, ufo: &WrappedUfoObject) {
+    if !info.read_only {
+        return;
     }
+    if let Ok(ufo) = ufo.read() {
+        // best effort, the object is still correct if nothing writes to it
+        let _ = protect(ufo.body_ptr(), info.body_size(), libc::PROT_READ);
+    }
+}
 
-    pub fn new_event_callback<F>(
-        &self,
-        callback: Option<Box<UfoEventConsumer>>,
-    ) -> Result<(), UfoInternalErr> {
-        self.core.new_event_callback(callback)
+// One flag per page overlapping [ptr, ptr + len), set if the page is in memory
+fn residency(ptr: *const u8, len: usize) -> std::io::Result<Vec<bool>> {
+    let page_size = system_page_size();
+    let offset = ptr as usize % page_size;
+    let start = unsafe { ptr.sub(offset) };
+    let pages = (offset + len).div_ceil(page_size);
+
+    let mut flags = vec![0u8; pages];
+    if pages > 0
+        && unsafe { libc::mincore(start as *mut c_void, pages * page_size, flags.as_mut_ptr()) }
+            != 0
+    {
+        return Err(std::io::Error::last_os_error());
     }
+    Ok(flags.into_iter().map(|f| f & 1 == 1).collect())
 }
 
-impl Drop for UfoCore {
-    fn drop(&mut self) {
-        self.core.shutdown();
+// `count` elements from `start` have to fit in `len`, without overflowing on the way
+fn check_range(start: usize, count: usize, len: usize) -> Result<(), UfoErr> {
+    match start.checked_add(count) {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(UfoErr::OutOfRange { start, count, len }),
     }
 }
 
-pub struct UfoHandle {
-    ufo: Option<WrappedUfoObject>,
+/// Bytes `copy_ufo_range` moves at a time
+pub const COPY_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Elements `debug_dump` prints before eliding the middle of a range
+pub const DEBUG_DUMP_LIMIT: usize = 64;
+
+/// Shared by every clone of a `UfoCore`, shuts the core down when the last one goes away
+struct CoreInner {
+    core: Arc<ufo_core::UfoCore>,
+    events: Arc<EventHub>,
+    // handles share this so they can count themselves out when freed
+    live_ufos: Arc<AtomicUsize>,
+    populate: Arc<PopulateCounters>,
+    writeback: Arc<WritebackCounters>,
+    verify_determinism: AtomicBool,
+    pacer: Arc<PopulatePacer>,
+    prefetcher: Arc<Prefetcher>,
+    #[cfg(feature = "backtrace")]
+    allocations: Arc<AllocationRegistry>,
+    // false once shut down, allocations hold it for reading so shutdown can check for live
+    // objects and close the core in one step
+    open: RwLock<bool>,
+    frees: Arc<PendingFrees>,
+    writeback_temp_path: PathBuf,
 }
 
-impl UfoHandle {
-    pub fn header_ptr(&self) -> Result<*mut std::ffi::c_void, UfoInternalErr> {
-        self.ufo.as_ref()
-            .ok_or(UfoInternalErr::UfoNotFound)
-            .and_then(|ufo| Ok(ufo.read()?.header_ptr()))
+impl Drop for CoreInner {
+    fn drop(&mut self) {
+        // best effort when the core was not shut down explicitly
+        let open = self.open.get_mut().unwrap_or_else(|e| e.into_inner());
+        if *open {
+            *open = false;
+            self.core.shutdown();
+        }
     }
+}
 
-    pub fn body_ptr(&self) -> Result<*mut std::ffi::c_void, UfoInternalErr> {
-        self.ufo.as_ref()
-            .ok_or(UfoInternalErr::UfoNotFound)
-            .and_then(|ufo| Ok(ufo.read()?.body_ptr()))
+#[derive(Clone)]
+pub struct UfoCore {
+    inner: Arc<CoreInner>,
+}
+
+impl std::fmt::Debug for UfoCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UfoCore").field("core", &"...").finish()
     }
+}
 
-    pub fn reset(&self) -> Result<(), UfoInternalErr> {
-        self.ufo.as_ref()
-        .ok_or(UfoInternalErr::UfoNotFound)
-        .and_then(|ufo| {
-            let waiter = ufo.write()?.reset()?;
-            waiter.wait();
-            Ok(())
+impl UfoCore {
+    /// Starts a core, failures are classified so the common environment problems explain
+    /// themselves
+    pub fn new_ufo_core(config: UfoCoreConfig) -> Result<UfoCore, UfoStartErr> {
+        let high_watermark = config.high_watermark;
+        let writeback_temp_path = PathBuf::from(&config.writeback_temp_path);
+        let frees = PendingFrees::new()?;
+        let prefetcher = Prefetcher::new(high_watermark)?;
+        let core = ufo_core::UfoCore::new(config)?;
+
+        // The core only takes one callback, install a dispatcher so several consumers can listen
+        let events = Arc::new(EventHub::new());
+        let hub = events.clone();
+        core.new_event_callback(Some(Box::new(move |event| hub.dispatch(event))))
+            .map_err(|e| Error::other(format!("{:?}", e)))?;
+
+        Ok(UfoCore {
+            inner: Arc::new(CoreInner {
+                core,
+                events,
+                live_ufos: Arc::new(AtomicUsize::new(0)),
+                populate: Arc::new(PopulateCounters::default()),
+                writeback: Arc::new(WritebackCounters::default()),
+                verify_determinism: AtomicBool::new(false),
+                pacer: Arc::new(PopulatePacer::new()),
+                prefetcher,
+                open: RwLock::new(true),
+                frees,
+                writeback_temp_path,
+                #[cfg(feature = "backtrace")]
+                allocations: Arc::new(AllocationRegistry::default()),
+            }),
         })
     }
 
-    pub fn free(mut self) -> Result<(), UfoInternalErr> {
-        self.ufo.take()
-            .ok_or(UfoInternalErr::UfoNotFound)
-            .and_then(|ufo| {
-                let waiter = ufo.write()?.free()?;
-                waiter.wait();
-                Ok(())
-            })
+    /// Lists every object that has not been freed yet along with the backtrace of its allocation
+    #[cfg(feature = "backtrace")]
+    pub fn leaked_objects_report(&self) -> String {
+        self.inner.allocations.report()
     }
-}
 
-impl Drop for UfoHandle {
-    #[allow(unused_must_use)]
-    fn drop(&mut self) {
-        // If the lock fails then there is something majorly wrong going on, don't panic inside a panic
-        if let Some(ufo) = self.ufo.take() {
-            if let Ok(mut ufo) = ufo.write() {
-                ufo.free(); // may have failed if the core is shutdown
-            }
-        }
+    /// Caps the combined rate at which populate functions of all objects on this core produce
+    /// data, `None` removes the cap
+    ///
+    /// Populate calls are delayed at chunk boundaries to stay under the rate, and the faulting
+    /// threads block for as long. Useful when populate reads from a rate limited upstream.
+    pub fn set_populate_rate_limit(&self, bytes_per_sec: Option<u64>) {
+        self.inner.pacer.set_limit(bytes_per_sec);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::Result;
-    use num::Integer;
-    use std::{
-        convert::{TryFrom, TryInto},
-        fmt::Debug,
-        mem::size_of,
-    };
-    use ufo_core::{UfoAllocateErr, UfoCoreConfig};
+    /// Check that populate returns the same data every time it fills a range, debug builds only
+    ///
+    /// Objects allocated while this is on keep a checksum of each populated range and record
+    /// the range in `UfoHandle::nondeterministic_ranges` when it is populated again (after
+    /// eviction) with different contents. `reset` forgets the checksums but not the recorded
+    /// ranges. In release builds this does nothing.
+    pub fn verify_populate_determinism(&self, enabled: bool) {
+        self.inner
+            .verify_determinism
+            .store(cfg!(debug_assertions) && enabled, Ordering::Release);
+    }
 
-    #[test]
-    fn core_starts() {
-        let config = UfoCoreConfig {
-            writeback_temp_path: "/tmp".to_string(),
-            high_watermark: 1024 * 1024 * 1024,
-            low_watermark: 512 * 1024 * 1024,
+    // Wraps the populate function so the handle and the core can observe it
+    fn instrument(
+        &self,
+        prototype: UfoObjectParams,
+        policy: PopulateFailurePolicy,
+    ) -> (UfoObjectParams, HandleState) {
+        let info = UfoInfo::from_params(&prototype);
+        let inner: Arc<UfoPopulateFn> = Arc::from(prototype.populate);
+        let verify = self.inner.verify_determinism.load(Ordering::Acquire);
+        let state = HandleState {
+            info,
+            populate: Arc::new(PopulateCounters::default()),
+            writeback: Arc::new(WritebackCounters::default()),
+            poison: Arc::new(PoisonState::new(policy)),
+            prefetcher: self.inner.prefetcher.clone(),
+            checksums: verify.then(|| Arc::new(PopulateChecksums::default())),
+            live_ufos: self.inner.live_ufos.clone(),
+            frees: self.inner.frees.clone(),
+            source: inner.clone(),
+            #[cfg(feature = "backtrace")]
+            site: self.inner.allocations.register(info),
         };
-        let core = UfoCore::new_ufo_core(config).expect("error getting core");
 
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        // objects without a listener keep none, so their writebacks go uncounted
+        let writeback_listener = prototype.writeback_listener.map(|listener| {
+            let counters = state.writeback.clone();
+            let core_counters = self.inner.writeback.clone();
+            Box::new(move |event: UfoWriteListenerEvent| {
+                if let UfoWriteListenerEvent::Writeback {
+                    start_idx, end_idx, ..
+                } = event
+                {
+                    let bytes = clamp_chunk(start_idx, end_idx, info.element_ct, info.stride)
+                        .map_or(0, |(_, bytes)| bytes as u64);
+                    counters.record(bytes);
+                    core_counters.record(bytes);
+                    stats::publish_writeback(bytes);
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("ufo.writeback", start_idx, end_idx).entered();
+                    return listener(event);
+                }
+                listener(event)
+            }) as Box<UfoWritebackListenerFn>
+        });
 
-        std::mem::drop(core);
+        let object_counters = state.populate.clone();
+        let core_counters = self.inner.populate.clone();
+        let checksums = state.checksums.clone();
+        let poison = state.poison.clone();
+        let pacer = self.inner.pacer.clone();
+        let stride = info.stride;
+        let prototype = UfoObjectParams {
+            populate: Box::new(move |start, end, fill| {
+                let (end, bytes) = match clamp_chunk(start, end, info.element_ct, stride) {
+                    Some(chunk) => chunk,
+                    None => return Ok(()),
+                };
+                pacer.pace(bytes);
+                let started = Instant::now();
+                // a panic must not unwind into the core's fault handler, it counts as a failure
+                let populated = {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("ufo.populate", start, end).entered();
+                    matches!(
+                        catch_unwind(AssertUnwindSafe(|| inner(start, end, fill))),
+                        Ok(Ok(()))
+                    )
+                };
+                let elapsed = started.elapsed();
+
+                object_counters.record(bytes as u64, elapsed);
+                core_counters.record(bytes as u64, elapsed);
+                stats::publish_populate(bytes as u64, elapsed);
+                if let (Some(checksums), true) = (&checksums, populated) {
+                    checksums.check(start, end, unsafe {
+                        std::slice::from_raw_parts(fill as *const u8, bytes)
+                    });
+                }
+
+                if !populated {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(start, end, "populate failed, range zero filled");
+                    // record the failure before the faulting thread can resume and look for it
+                    poison.poison(start..end);
+                    unsafe { std::ptr::write_bytes(fill, 0, bytes) };
+                }
+                Ok(())
+            }),
+            writeback_listener,
+            ..prototype
+        };
+
+        (prototype, state)
     }
 
-    fn basic_test_object<T>(
-        header_size: usize,
-        body_size: usize,
-        min_load: usize,
-        read_only: bool,
-    ) -> Result<(UfoCore, UfoHandle), UfoAllocateErr>
+    pub fn new_ufo(&self, prototype: UfoObjectParams) -> Result<UfoHandle, UfoAllocateErr> {
+        self.new_ufo_with_policy(prototype, PopulateFailurePolicy::default())
+    }
+
+    /// Like `new_ufo`, choosing what a populate error does to the object
+    pub fn new_ufo_with_policy(
+        &self,
+        prototype: UfoObjectParams,
+        policy: PopulateFailurePolicy,
+    ) -> Result<UfoHandle, UfoAllocateErr> {
+        let _open = self.open_for_allocation()?;
+        let (prototype, state) = self.instrument(prototype, policy);
+        let ufo = self.inner.core.allocate_ufo(prototype.new_config())?;
+        Ok(UfoHandle::new(Some(ufo), None, state))
+    }
+
+    /// Allocates on a background thread so that setup overlaps with other work
+    ///
+    /// The handle can be used straight away, its methods block until the allocation has
+    /// finished. If the allocation fails the waiter reports why and the handle behaves as if it
+    /// had been freed.
+    pub fn new_ufo_deferred(
+        &self,
+        prototype: UfoObjectParams,
+    ) -> (UfoHandle, UfoWaiter<Result<(), UfoAllocateErr>>) {
+        // the handle counts as live before this lets go, so shutdown cannot overtake the
+        // allocation
+        let open = self.open_for_allocation();
+        let (prototype, state) = self.instrument(prototype, PopulateFailurePolicy::default());
+        let config = prototype.new_config();
+        let (object_tx, object_rx) = channel::bounded(1);
+        let (done, waiter) = UfoWaiter::new();
+
+        let core = self.inner.core.clone();
+        let refused = open.is_err();
+        std::thread::spawn(move || {
+            let allocated = if refused {
+                Err(UfoAllocateErr::MessageSendError)
+            } else {
+                core.allocate_ufo(config)
+            };
+            match allocated {
+                Ok(ufo) => {
+                    let _ = object_tx.send(Some(ufo));
+                    let _ = done.send(Ok(()));
+                }
+                Err(e) => {
+                    let _ = object_tx.send(None);
+                    let _ = done.send(Err(e));
+                }
+            }
+        });
+
+        let handle = UfoHandle::new(None, Some(object_rx), state);
+        drop(open);
+        (handle, waiter)
+    }
+
+    /// Allocates an object for the duration of `f`, it is freed before this returns
+    ///
+    /// If `f` panics the handle is still dropped (and the object freed) while unwinding.
+    pub fn with_ufo<R, F>(&self, prototype: UfoObjectParams, f: F) -> Result<R, UfoAllocateErr>
     where
-        T: Sized + Integer + TryFrom<usize>,
-        <T as TryFrom<usize>>::Error: Debug,
+        F: FnOnce(&UfoHandle) -> R,
     {
-        let config = UfoCoreConfig {
-            writeback_temp_path: "/tmp".to_string(),
-            high_watermark: 1024 * 1024 * 1024,
-            low_watermark: 512 * 1024 * 1024,
-        };
-        let core = UfoCore::new_ufo_core(config).expect("error getting core");
+        let ufo = self.new_ufo(prototype)?;
+        let result = f(&ufo);
+        // Same best effort as dropping the handle, but waits for the free to complete
+        let _ = ufo.free();
+        Ok(result)
+    }
 
-        let ufo_params = UfoObjectParams {
-            header_size,
-            stride: size_of::<T>(),
-            min_load_ct: Some(min_load),
-            read_only,
-            element_ct: body_size,
-            populate: Box::new(|start, end, fill| {
-                let slice = unsafe {
-                    std::slice::from_raw_parts_mut::<T>(fill.cast(), size_of::<T>() * (end - start))
-                };
+    /// Frees many objects at once
+    ///
+    /// Every free is queued with the core before this returns, so writebacks and unmaps for
+    /// different objects overlap, and the one waiter completes once all of them have. Its
+    /// result holds one entry per handle in the order given. Handles that could not be freed
+    /// are dropped, which retries the free on a best effort basis.
+    pub fn free_all(&self, handles: Vec<UfoHandle>) -> UfoWaiter<Vec<Result<(), UfoErr>>> {
+        let frees: Vec<_> = handles.iter().map(UfoHandle::start_free).collect();
+        std::mem::drop(handles);
+
+        let (done, waiter) = UfoWaiter::new();
+        std::thread::spawn(move || {
+            let results = frees.into_iter().map(|free| free.map(|wait| wait())).collect();
+            let _ = done.send(results);
+        });
+        waiter
+    }
+
+    /// Shuts the core down now instead of when the last clone is dropped
+    ///
+    /// Refuses with `UfoErr::HandlesOutstanding` while any object is still alive, so no mapping
+    /// is left dangling; free them first. Frees still writing back, including those started by
+    /// dropping a handle, are waited for and the writeback store is synced to disk, an IO error
+    /// doing so is returned and leaves the core running. Other clones of the core can no longer
+    /// allocate once this succeeds.
+    pub fn shutdown(self) -> Result<(), UfoErr> {
+        // held throughout, so no clone can allocate between the check and the shutdown
+        let mut open = self.inner.open.write()?;
+        let live = self.ufo_count();
+        if live > 0 {
+            return Err(UfoErr::HandlesOutstanding(live));
+        }
+        if *open {
+            self.inner.frees.wait_idle();
+            sync_filesystem(&self.inner.writeback_temp_path)?;
+            *open = false;
+            self.inner.core.shutdown();
+        }
+        Ok(())
+    }
+
+    // Held by every allocation until its handle is counted, refuses once the core is shut down
+    fn open_for_allocation(&self) -> Result<RwLockReadGuard<'_, bool>, UfoAllocateErr> {
+        let open = self.inner.open.read().unwrap_or_else(|e| e.into_inner());
+        if *open {
+            Ok(open)
+        } else {
+            Err(UfoAllocateErr::MessageSendError)
+        }
+    }
+
+    /// Exercises populate, writes and reset on a small object, for readiness probes
+    ///
+    /// Success means userfaultfd is usable in this environment and faults reach populate.
+    /// Forcing an eviction is up to the core's watermarks, so the reload step uses `reset`
+    /// instead.
+    pub fn self_test(&self) -> Result<(), UfoErr> {
+        let element_ct = 4 * system_page_size();
+        let populated = Arc::new(AtomicUsize::new(0));
+        let calls = populated.clone();
+        let o = self.new_ufo(UfoObjectParams {
+            header_size: 0,
+            stride: 1,
+            min_load_ct: None,
+            read_only: false,
+            element_ct,
+            populate: Box::new(move |start, end, fill| {
+                calls.fetch_add(1, Ordering::AcqRel);
                 for idx in start..end {
-                    slice[idx - start] = idx.try_into().unwrap();
+                    unsafe { fill.add(idx - start).write(idx as u8) };
                 }
-
                 Ok(())
             }),
             writeback_listener: None,
-        };
+        })?;
 
-        let o = core.new_ufo(ufo_params)?;
+        let body = o.body_ptr()?.cast::<u8>();
+        let read = |idx: usize| unsafe { std::ptr::read_volatile(body.add(idx)) };
+        if read(element_ct - 1) != (element_ct - 1) as u8 {
+            return Err(UfoErr::SelfTestFailed("populated data read back wrong"));
+        }
+        if populated.load(Ordering::Acquire) == 0 {
+            return Err(UfoErr::SelfTestFailed("fault did not reach populate"));
+        }
 
-        Ok((core, o))
+        unsafe { std::ptr::write_volatile(body.add(1), 0xAA) };
+        if read(1) != 0xAA {
+            return Err(UfoErr::SelfTestFailed("write was not visible"));
+        }
+
+        o.reset()?;
+        if read(1) != 1 {
+            return Err(UfoErr::SelfTestFailed("reset did not repopulate"));
+        }
+
+        o.free()?;
+        Ok(())
     }
 
-    #[test]
-    fn it_works() -> Result<(), UfoAllocateErr> {
-        let (core, o) = basic_test_object::<u32>(0, 1000 * 1000, 4096, false)?;
+    /// Makes a new object with the same contents as the read only object `parent`
+    ///
+    /// The snapshot shares the parent's populate function rather than its pages, so nothing is
+    /// copied up front and writes to the snapshot never reach the parent. It does not depend on
+    /// the parent staying alive. The header is copied as it is now.
+    ///
+    /// This lives on the core rather than on `UfoHandle` because a handle has no way back to
+    /// the core that allocated it, and the snapshot is a new allocation. Only read only
+    /// parents are supported: their populate function always gives the current contents, so
+    /// calling it again is a faithful copy. A writable parent's dirty pages would have to be
+    /// shared copy on write, which ufo_core cannot do, so those are refused with
+    /// `UfoErr::Writable`.
+    pub fn snapshot(&self, parent: &UfoHandle) -> Result<UfoHandle, UfoErr> {
+        let info = parent.info;
+        if !info.read_only {
+            return Err(UfoErr::Writable);
+        }
+        let (_parent_slot, header) = parent.locked_header()?;
+        let header = unsafe { std::slice::from_raw_parts(header, info.header_size) };
 
-        let arr = unsafe {
-            std::slice::from_raw_parts_mut(o.body_ptr().unwrap().cast::<u32>(), 1000 * 1000)
+        let source = parent.source.clone();
+        let params = UfoObjectParams {
+            header_size: info.header_size,
+            stride: info.stride,
+            min_load_ct: info.min_load_ct,
+            read_only: false,
+            element_ct: info.element_ct,
+            populate: Box::new(move |start, end, fill| source(start, end, fill)),
+            writeback_listener: None,
         };
+        let snapshot = self.new_ufo_with_policy(params, parent.failure_policy())?;
+        {
+            let (_slot, to) = snapshot.locked_header()?;
+            unsafe { std::ptr::copy_nonoverlapping(header.as_ptr(), to, header.len()) };
+        }
+        Ok(snapshot)
+    }
 
-        for x in 0..1000 * 1000 {
-            assert_eq!(x as u32, arr[x]);
+    /// Copies `count` elements from `src` starting at `src_start` into `dst` at `dst_start`
+    ///
+    /// The copy runs chunk by chunk, each source chunk is faulted in and written to the
+    /// destination before moving on, so the core can evict already copied chunks instead of
+    /// holding both ranges in memory. Overlapping ranges within one object are handled like
+    /// `ptr::copy`. Read only and sealed destinations are refused, as is either range running
+    /// past the end of its object.
+    pub fn copy_ufo_range(
+        &self,
+        src: &UfoHandle,
+        src_start: usize,
+        dst: &UfoHandle,
+        dst_start: usize,
+        count: usize,
+    ) -> Result<(), UfoErr> {
+        let stride = src.info.stride;
+        if stride != dst.info.stride {
+            return Err(UfoErr::StrideMismatch {
+                src: stride,
+                dst: dst.info.stride,
+            });
         }
+        dst.writable()?;
+        check_range(src_start, count, src.info.element_ct)?;
+        check_range(dst_start, count, dst.info.element_ct)?;
 
-        std::mem::drop(core);
+        // both objects are held for the whole copy, a copy within one object only takes its
+        // lock once so a free waiting on it cannot wedge the second read, and two objects are
+        // always locked lower address first so copies in opposite directions cannot deadlock
+        // against a free queued on either of them
+        let (_first_slot, _second_slot, from, to) = if Arc::ptr_eq(&src.ufo, &dst.ufo) {
+            let (slot, body) = src.locked_body::<u8>()?;
+            (slot, None, body, body)
+        } else if Arc::as_ptr(&src.ufo) < Arc::as_ptr(&dst.ufo) {
+            let (src_slot, from) = src.locked_body::<u8>()?;
+            let (dst_slot, to) = dst.locked_body::<u8>()?;
+            (src_slot, Some(dst_slot), from, to)
+        } else {
+            let (dst_slot, to) = dst.locked_body::<u8>()?;
+            let (src_slot, from) = src.locked_body::<u8>()?;
+            (dst_slot, Some(src_slot), from, to)
+        };
+        let from = unsafe { from.add(src_start * stride) };
+        let to = unsafe { to.add(dst_start * stride) };
+        let chunk = (COPY_CHUNK_BYTES / stride).max(1);
+        let chunks = (0..count).step_by(chunk).map(|start| (start, chunk.min(count - start)));
+
+        let mut copy = |(start, len): (usize, usize)| unsafe {
+            std::ptr::copy(from.add(start * stride), to.add(start * stride), len * stride)
+        };
+        // copying back to front keeps an overlapping source intact until it has been read
+        if (to as usize) > (from as usize) {
+            chunks.rev().for_each(&mut copy);
+        } else {
+            chunks.for_each(&mut copy);
+        }
         Ok(())
     }
 
-    #[test]
-    fn free_ufo() -> Result<(), UfoInternalErr> {
-        // use stderrlog;
-        // stderrlog::new()
-        //     // .module("ufo_core")
-        //     .verbosity(4)
-        //     .timestamp(stderrlog::Timestamp::Microsecond)
-        //     .init()
-        //     .unwrap();
+    pub fn new_event_callback(
+        &self,
+        callback: Option<Box<UfoEventConsumer>>,
+    ) -> Result<(), UfoInternalErr> {
+        self.inner.events.set_callback(callback);
+        Ok(())
+    }
 
-        let (core, o) = basic_test_object::<u32>(0, 1000 * 1000, 4096, false)?;
+    /// Pull based alternative to `new_event_callback`
+    ///
+    /// Each receiver buffers up to `EVENT_QUEUE_CAPACITY` events, when a receiver falls behind
+    /// the oldest events are discarded and counted in `dropped_events` so fault handling never
+    /// blocks on a slow consumer. Dropping the receiver and all its clones unsubscribes it.
+    pub fn event_receiver(&self) -> EventReceiver {
+        self.inner.events.receiver()
+    }
 
-        o.free()?;
+    /// Total number of events discarded because a receiver was full
+    pub fn dropped_events(&self) -> u64 {
+        self.inner.events.dropped_events()
+    }
 
-        std::mem::drop(core);
+    /// Number of objects allocated through this core that have not been freed yet
+    pub fn ufo_count(&self) -> usize {
+        self.inner.live_ufos.load(Ordering::Acquire)
+    }
+
+    /// Takes back a handle given up with `UfoHandle::into_raw`
+    ///
+    /// Panics if the handle was allocated by a different core.
+    ///
+    /// # Safety
+    /// `raw` must come from `into_raw` and must not have been taken back already.
+    pub unsafe fn handle_from_raw(&self, raw: UfoHandleRaw) -> UfoHandle {
+        let handle = *Box::from_raw(raw.0);
+        assert!(
+            Arc::ptr_eq(&handle.live_ufos, &self.inner.live_ufos),
+            "handle belongs to a different core"
+        );
+        handle
+    }
+
+    /// Average rate in bytes per second at which populate functions produced data, across
+    /// every object allocated through this core
+    pub fn populate_throughput_bps(&self) -> f64 {
+        self.inner.populate.throughput_bps()
+    }
+
+    /// Activity of every object this core has allocated, freed ones included
+    pub fn stats(&self) -> UfoStats {
+        let mut stats = UfoStats::default();
+        self.inner.populate.fill(&mut stats);
+        self.inner.writeback.fill(&mut stats);
+        stats
+    }
+
+    /// Granularity at which the core maps, populates and evicts memory
+    pub fn page_size(&self) -> usize {
+        system_page_size()
+    }
+}
+
+/// Layout of an object as requested when it was allocated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UfoInfo {
+    pub header_size: usize,
+    pub stride: usize,
+    pub element_ct: usize,
+    pub min_load_ct: Option<usize>,
+    pub read_only: bool,
+}
+
+impl UfoInfo {
+    fn from_params(params: &UfoObjectParams) -> Self {
+        UfoInfo {
+            header_size: params.header_size,
+            stride: params.stride,
+            element_ct: params.element_ct,
+            min_load_ct: params.min_load_ct,
+            read_only: params.read_only,
+        }
+    }
+
+    /// Size of the body in bytes, not counting page padding
+    pub fn body_size(&self) -> usize {
+        self.stride * self.element_ct
+    }
+}
+
+// Everything a handle tracks besides the object itself, set up before allocation
+struct HandleState {
+    info: UfoInfo,
+    populate: Arc<PopulateCounters>,
+    writeback: Arc<WritebackCounters>,
+    poison: Arc<PoisonState>,
+    prefetcher: Arc<Prefetcher>,
+    checksums: Option<Arc<PopulateChecksums>>,
+    live_ufos: Arc<AtomicUsize>,
+    frees: Arc<PendingFrees>,
+    source: Arc<UfoPopulateFn>,
+    #[cfg(feature = "backtrace")]
+    site: AllocationSite,
+}
+
+pub struct UfoHandle {
+    // None once the object has been freed, so every later call fails with UfoNotFound
+    // shared with background prefetches, which check it before touching the body
+    ufo: Arc<RwLock<Option<WrappedUfoObject>>>,
+    // held for reading by a running prefetch, free and drop take it for writing before the slot
+    // so they wait for the prefetch instead of mistaking it for a view
+    prefetches: Arc<RwLock<()>>,
+    // Set while a deferred allocation is still running, None means the allocation failed
+    pending: Mutex<Option<Receiver<Option<WrappedUfoObject>>>>,
+    info: UfoInfo,
+    sealed: AtomicBool,
+    populate: Arc<PopulateCounters>,
+    writeback: Arc<WritebackCounters>,
+    poison: Arc<PoisonState>,
+    prefetcher: Arc<Prefetcher>,
+    checksums: Option<Arc<PopulateChecksums>>,
+    live_ufos: Arc<AtomicUsize>,
+    frees: Arc<PendingFrees>,
+    // the populate function as the caller supplied it, before instrumentation
+    source: Arc<UfoPopulateFn>,
+    // dropping leaves the object allocated, only an explicit free releases it
+    detached: bool,
+    #[cfg(feature = "backtrace")]
+    site: AllocationSite,
+}
+
+/// A handle given up with `UfoHandle::into_raw`, for runtimes that own objects through pointers
+///
+/// Holding one keeps the object allocated. It only becomes a handle again through
+/// `UfoCore::handle_from_raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct UfoHandleRaw(*mut UfoHandle);
+
+impl UfoHandleRaw {
+    /// The pointer to store on the foreign side
+    pub fn as_ptr(self) -> *mut c_void {
+        self.0.cast()
+    }
+
+    /// # Safety
+    /// `ptr` has to come from `as_ptr` on a raw handle not yet passed to `handle_from_raw`
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+        UfoHandleRaw(ptr.cast())
+    }
+}
+
+impl UfoHandle {
+    fn new(
+        ufo: Option<WrappedUfoObject>,
+        pending: Option<Receiver<Option<WrappedUfoObject>>>,
+        state: HandleState,
+    ) -> Self {
+        state.live_ufos.fetch_add(1, Ordering::AcqRel);
+        stats::publish_ufo_count_change(1.0);
+        if let Some(ufo) = &ufo {
+            protect_read_only(&state.info, ufo);
+            state.prefetcher.register(ufo, state.info.body_size());
+        }
+        UfoHandle {
+            ufo: Arc::new(RwLock::new(ufo)),
+            prefetches: Arc::new(RwLock::new(())),
+            pending: Mutex::new(pending),
+            info: state.info,
+            sealed: AtomicBool::new(false),
+            populate: state.populate,
+            writeback: state.writeback,
+            poison: state.poison,
+            prefetcher: state.prefetcher,
+            checksums: state.checksums,
+            live_ufos: state.live_ufos,
+            frees: state.frees,
+            source: state.source,
+            detached: false,
+            #[cfg(feature = "backtrace")]
+            site: state.site,
+        }
+    }
+
+    /// Gives up the handle without freeing the object, see `UfoCore::handle_from_raw`
+    ///
+    /// Pointers into the object stay valid until the handle is taken back and freed.
+    pub fn into_raw(self) -> UfoHandleRaw {
+        UfoHandleRaw(Box::into_raw(Box::new(self)))
+    }
+
+    /// Stops dropping the handle from freeing the object
+    ///
+    /// For objects whose lifetime is decided elsewhere, such as a host runtime's finalizer. The
+    /// object is then only released by `free` or when the core goes away, a detached handle
+    /// dropped without a `free` leaks its object and keeps it counted in `ufo_count`.
+    pub fn detach(&mut self) {
+        self.detached = true;
+    }
+
+    // Blocks until a deferred allocation has completed
+    fn resolve_pending(&self) -> Result<(), UfoInternalErr> {
+        let mut pending = self.pending.lock()?;
+        if let Some(object) = pending.take() {
+            match object.recv().ok().flatten() {
+                Some(ufo) => {
+                    protect_read_only(&self.info, &ufo);
+                    self.prefetcher.register(&ufo, self.info.body_size());
+                    *self.ufo.write()? = Some(ufo);
+                }
+                None => self.count_out(),
+            }
+        }
+        Ok(())
+    }
+
+    // The object is gone, either freed or never allocated
+    fn count_out(&self) {
+        self.live_ufos.fetch_sub(1, Ordering::AcqRel);
+        stats::publish_ufo_count_change(-1.0);
+        #[cfg(feature = "backtrace")]
+        self.site.release();
+    }
+
+    fn slot(&self) -> Result<RwLockReadGuard<'_, Option<WrappedUfoObject>>, UfoInternalErr> {
+        self.resolve_pending()?;
+        Ok(self.ufo.read()?)
+    }
+
+    pub fn info(&self) -> &UfoInfo {
+        &self.info
+    }
+
+    pub fn stats(&self) -> UfoStats {
+        let mut stats = UfoStats::default();
+        self.populate.fill(&mut stats);
+        self.writeback.fill(&mut stats);
+        stats
+    }
+
+    /// Zeroes the stats, including `populate_throughput_bps`, to measure one phase on its own
+    pub fn reset_stats(&self) {
+        self.populate.reset();
+        self.writeback.reset();
+    }
+
+    /// Whether a populate call for this object has failed
+    ///
+    /// Under `PopulateFailurePolicy::ZeroFill` a `reset` clears this. Under `Poison` it stays
+    /// set until the object is freed.
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.is_poisoned()
+    }
+
+    /// Element ranges whose populate call failed, sorted and merged where they touch
+    ///
+    /// These are the ranges that read as zeros. The list is cleared along with the poison.
+    pub fn failed_ranges(&self) -> Vec<Range<usize>> {
+        self.poison.failed_ranges()
+    }
+
+    /// Ranges that populated differently the second time, see
+    /// `UfoCore::verify_populate_determinism`
+    ///
+    /// Always empty for objects allocated without verification.
+    pub fn nondeterministic_ranges(&self) -> Vec<Range<usize>> {
+        self.checksums
+            .as_ref()
+            .map(|checksums| checksums.mismatches())
+            .unwrap_or_default()
+    }
+
+    pub fn failure_policy(&self) -> PopulateFailurePolicy {
+        self.poison.policy()
+    }
+
+    // Refuses to operate on objects killed by a populate failure
+    fn alive(&self) -> Result<(), UfoErr> {
+        if self.poison.is_dead() {
+            return Err(UfoErr::Poisoned);
+        }
+        Ok(())
+    }
+
+    // Refuses writes to a write protected body, every safe write path has to check this first
+    fn writable(&self) -> Result<(), UfoErr> {
+        if self.info.read_only {
+            return Err(UfoErr::ReadOnly);
+        }
+        if self.is_sealed() {
+            return Err(UfoErr::Sealed);
+        }
+        Ok(())
+    }
+
+    /// Where this object was allocated
+    #[cfg(feature = "backtrace")]
+    pub fn allocation_site(&self) -> &std::backtrace::Backtrace {
+        self.site.backtrace()
+    }
+
+    /// Average rate in bytes per second at which this object's populate function produced data
+    ///
+    /// High throughput means faulting on demand is cheap, low throughput suggests prefetching
+    /// ahead of access pays off.
+    pub fn populate_throughput_bps(&self) -> f64 {
+        self.populate.throughput_bps()
+    }
+
+    pub fn header_ptr(&self) -> Result<*mut c_void, UfoInternalErr> {
+        self.slot()?.as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)
+            .and_then(|ufo| Ok(ufo.read()?.header_ptr()))
+    }
+
+    pub fn body_ptr(&self) -> Result<*mut c_void, UfoInternalErr> {
+        self.slot()?.as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)
+            .and_then(|ufo| Ok(ufo.read()?.body_ptr()))
+    }
+
+    /// Discards the body's contents, they are populated again on next access
+    ///
+    /// Waits for outstanding views such as `UfoSlice` to be dropped, so the old contents are
+    /// never replaced under one. Calling it from a thread holding a view deadlocks.
+    pub fn reset(&self) -> Result<(), UfoInternalErr> {
+        let wait = self.start_reset()?;
+        wait();
+        Ok(())
+    }
+
+    /// Like `reset` but stops waiting after `timeout`
+    ///
+    /// On timeout the reset is still underway and completes on its own, `UfoErr::Timeout`
+    /// carries a waiter for it.
+    pub fn reset_timeout(&self, timeout: Duration) -> Result<(), UfoErr> {
+        self.reset_begin()?
+            .wait_timeout(timeout)
+            .map_err(UfoErr::Timeout)
+    }
+
+    /// Starts a reset without waiting for it, the waiter completes once the reset has
+    pub fn reset_begin(&self) -> Result<UfoWaiter<()>, UfoInternalErr> {
+        Ok(UfoWaiter::in_background(self.start_reset()?))
+    }
+
+    // Hands the reset to the core, the returned closure blocks until it has completed
+    fn start_reset(&self) -> Result<impl FnOnce() + Send + 'static, UfoInternalErr> {
+        self.resolve_pending()?;
+        // held for writing like free, so the reset waits for every view of the old contents
+        let waiter = self.ufo.write()?.as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)
+            .and_then(|ufo| ufo.write()?.reset())?;
+        let checksums = self.checksums.clone();
+        let poison = self.poison.clone();
+
+        Ok(move || {
+            waiter.wait();
+            if let Some(checksums) = &checksums {
+                checksums.clear();
+            }
+            poison.on_reset();
+        })
+    }
+
+    /// Replaces every element with `f` applied to it, front to back
+    ///
+    /// Elements are transformed in order, so pages that are done can be written back and
+    /// evicted by the core while the rest are still faulted in, and the object never needs to
+    /// be resident all at once.
+    pub fn map_in_place<T, F>(&self, f: F) -> Result<(), UfoErr>
+    where
+        T: Pod,
+        F: Fn(T) -> T,
+    {
+        self.check_element::<T>()?;
+        self.writable()?;
+
+        let (_slot, body) = self.locked_body::<T>()?;
+        for idx in 0..self.info.element_ct {
+            unsafe {
+                let element = body.add(idx);
+                element.write(f(element.read()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the whole body into ordinary heap memory and frees the object
+    ///
+    /// Every element is read, so the object should fit in memory. The object is only freed once
+    /// the copy is complete, on an error the object is dropped like any other handle.
+    pub fn into_boxed_slice<T>(self) -> Result<Box<[T]>, UfoErr>
+    where
+        T: Pod,
+    {
+        self.check_element::<T>()?;
+        let slot = self.slot()?;
+        let body = slot.as_ref().ok_or(UfoInternalErr::UfoNotFound)?.read()?.body_ptr();
+        let boxed: Box<[T]> =
+            unsafe { std::slice::from_raw_parts(body.cast(), self.info.element_ct) }.into();
+        drop(slot);
+        self.free()?;
+        Ok(boxed)
+    }
+
+    /// 64 bit xxHash of the body's bytes
+    ///
+    /// The body is read front to back, so the core can evict hashed pages as it goes. The hash
+    /// only depends on the contents, not on which pages happened to be resident.
+    pub fn content_hash(&self) -> Result<u64, UfoErr> {
+        let mut hasher = XxHash64::with_seed(0);
+        for chunk in self.as_bytes()?.chunks(COPY_CHUNK_BYTES) {
+            hasher.write(chunk);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// Populates elements `start..end` on a background thread ahead of access
+    ///
+    /// Pages that are already resident are skipped, and at most enough pages to bring the
+    /// residency of every object on the core up to its high watermark are faulted in. The
+    /// waiter reports how many elements were brought in. Prefetches run one at a time on a
+    /// thread shared by the whole core. Freeing the object waits for a prefetch of it that is
+    /// already running, one still queued then brings nothing in.
+    pub fn prefetch(&self, start: usize, end: usize) -> Result<UfoWaiter<usize>, UfoInternalErr> {
+        assert!(
+            start <= end && end <= self.info.element_ct,
+            "range {}..{} out of bounds for {} elements",
+            start,
+            end,
+            self.info.element_ct
+        );
+        let body = self.body_ptr()?.cast::<u8>();
+        Ok(self.prefetch_body(body, start, end))
+    }
+
+    // Prefetches from a body the caller already looked up, so it can be called with the slot held
+    fn prefetch_body(&self, body: *const u8, start: usize, end: usize) -> UfoWaiter<usize> {
+        let (done, waiter) = UfoWaiter::new();
+        self.prefetcher.queue(PrefetchJob {
+            slot: self.ufo.clone(),
+            running: self.prefetches.clone(),
+            body: body as usize,
+            body_size: self.info.body_size(),
+            stride: self.info.stride,
+            range: start..end,
+            done,
+        });
+        waiter
+    }
+
+    /// Whether every page holding element `idx` is in memory, so reading it will not fault
+    pub fn is_resident(&self, idx: usize) -> Result<bool, UfoErr> {
+        assert!(idx < self.info.element_ct, "index {} out of bounds", idx);
+        let (_slot, body) = self.locked_body::<u8>()?;
+        self.resident_in(body, idx)
+    }
+
+    // Residency of element `idx` of a body the caller holds
+    fn resident_in(&self, body: *const u8, idx: usize) -> Result<bool, UfoErr> {
+        let element = unsafe { body.add(idx * self.info.stride) };
+        Ok(residency(element, self.info.stride)?.into_iter().all(|r| r))
+    }
+
+    /// Index and value of every element that is in memory, without faulting in the rest
+    ///
+    /// Residency is sampled once up front, an element evicted while iterating is faulted back in
+    /// when it is read. The iterator holds the object like `as_slice` does.
+    pub fn iter_resident<T>(&self) -> Result<impl Iterator<Item = (usize, T)> + '_, UfoErr>
+    where
+        T: Pod,
+    {
+        // the view checks the element size
+        let view = self.as_slice::<T>()?;
+        let pages = residency(view.as_ptr().cast(), self.info.body_size())?;
+        let page_size = system_page_size();
+        let offset = view.as_ptr() as usize % page_size;
+        let stride = self.info.stride;
+
+        Ok((0..self.info.element_ct).filter_map(move |idx| {
+            let first = (offset + idx * stride) / page_size;
+            let last = (offset + (idx + 1) * stride - 1) / page_size;
+            if pages[first..=last].iter().all(|r| *r) {
+                Some((idx, view[idx]))
+            } else {
+                None
+            }
+        }))
+    }
+
+    /// Writes one `index: value` line per element of `range`, for inspecting an object in tests
+    ///
+    /// Elements that are not resident are printed as such instead of being read, so dumping does
+    /// not populate the object. Ranges longer than `DEBUG_DUMP_LIMIT` only show their first and
+    /// last elements.
+    pub fn debug_dump<T, W>(&self, range: Range<usize>, w: &mut W) -> Result<(), UfoErr>
+    where
+        T: Pod + Debug,
+        W: Write,
+    {
+        self.check_element::<T>()?;
+        check_range(range.start, range.len(), self.info.element_ct)?;
+
+        let (_slot, body) = self.locked_body::<T>()?;
+        let dump = |w: &mut W, idx: usize| -> Result<(), UfoErr> {
+            if self.resident_in(body.cast(), idx)? {
+                writeln!(w, "{}: {:?}", idx, unsafe { &*body.add(idx) })?;
+            } else {
+                writeln!(w, "{}: <not resident>", idx)?;
+            }
+            Ok(())
+        };
+
+        if range.len() <= DEBUG_DUMP_LIMIT {
+            for idx in range {
+                dump(w, idx)?;
+            }
+        } else {
+            let half = DEBUG_DUMP_LIMIT / 2;
+            for idx in range.start..range.start + half {
+                dump(w, idx)?;
+            }
+            writeln!(w, "... {} elements elided ...", range.len() - 2 * half)?;
+            for idx in range.end - half..range.end {
+                dump(w, idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Overwrites the start of the header, then resets the body
+    ///
+    /// For objects whose populate function reads its parameters from the header: the body is
+    /// regenerated from the new header on next access. Panics if `new_header` is longer than
+    /// the header.
+    pub fn reset_with_header(&self, new_header: &[u8]) -> Result<(), UfoInternalErr> {
+        assert!(
+            new_header.len() <= self.info.header_size,
+            "{} byte header does not fit in {} bytes",
+            new_header.len(),
+            self.info.header_size
+        );
+        let slot = self.slot()?;
+        let header = slot.as_ref().ok_or(UfoInternalErr::UfoNotFound)?.read()?.header_ptr();
+        unsafe {
+            std::ptr::copy_nonoverlapping(new_header.as_ptr(), header.cast(), new_header.len())
+        };
+        drop(slot);
+        self.reset()
+    }
+
+    /// Makes the body read only without giving up the handle
+    ///
+    /// Resident pages are protected right away and pages populated afterwards are mapped read
+    /// only, so any later write to the body raises SIGSEGV. Reads, `reset` and `free` keep
+    /// working as before.
+    pub fn seal(&self) -> Result<(), UfoErr> {
+        let slot = self.slot()?;
+        let ufo = slot.as_ref().ok_or(UfoInternalErr::UfoNotFound)?.read()?;
+        protect(ufo.body_ptr(), self.info.body_size(), libc::PROT_READ)?;
+        self.sealed.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::Acquire)
+    }
+
+    /// Read only objects have a write protected body, writing through a raw pointer is a SIGSEGV
+    pub fn is_read_only(&self) -> bool {
+        self.info.read_only
+    }
+
+    /// Frees the object, any further use of the handle returns `UfoNotFound`
+    ///
+    /// Fails with `UfoErr::InUse` while a view such as `UfoSlice` is alive, on any thread, and
+    /// leaves the object allocated so the free can be retried. Raw pointers from `body_ptr` and
+    /// `header_ptr` dangle once this returns.
+    pub fn free(&self) -> Result<(), UfoErr> {
+        let wait = self.start_free()?;
+        wait();
+        Ok(())
+    }
+
+    /// Like `free` but stops waiting after `timeout`
+    ///
+    /// On timeout the handle has already let go of the object, which the core finishes freeing
+    /// on its own. `UfoErr::Timeout` carries a waiter for it.
+    pub fn free_timeout(&self, timeout: Duration) -> Result<(), UfoErr> {
+        self.free_begin()?
+            .wait_timeout(timeout)
+            .map_err(UfoErr::Timeout)
+    }
+
+    /// Starts a free without waiting for it
+    ///
+    /// The handle lets go of the object straight away, the waiter completes once the core has
+    /// finished freeing it.
+    pub fn free_begin(&self) -> Result<UfoWaiter<()>, UfoErr> {
+        Ok(UfoWaiter::in_background(self.start_free()?))
+    }
+
+    // Hands the free to the core, the returned closure blocks until it has completed
+    fn start_free(&self) -> Result<impl FnOnce() + Send + 'static, UfoErr> {
+        self.resolve_pending()?;
+        let _prefetches = self.prefetches.write()?;
+        // never waits for a view, the thread holding it could be this one
+        let mut slot = match self.ufo.try_write() {
+            Ok(slot) => slot,
+            Err(TryLockError::WouldBlock) => return Err(UfoErr::InUse),
+            Err(TryLockError::Poisoned(e)) => return Err(e.into()),
+        };
+        // counted before the object is counted out, so shutdown always sees one of the two
+        let in_flight = self.frees.start();
+        let waiter = slot.as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)
+            .and_then(|ufo| ufo.write()?.free())?;
+        // only forget the object once the core accepted the free, so a failed free can be retried
+        if let Some(ufo) = slot.take() {
+            self.prefetcher.unregister(&ufo);
+        }
+        self.count_out();
+
+        Ok(move || {
+            waiter.wait();
+            drop(in_flight);
+        })
+    }
+}
+
+impl Drop for UfoHandle {
+    #[allow(unused_must_use)]
+    fn drop(&mut self) {
+        // If the lock fails then there is something majorly wrong going on, don't panic inside a panic
+        let _ = self.resolve_pending();
+
+        // waits for any prefetch still touching the object
+        let _prefetches = match self.prefetches.write() {
+            Ok(prefetches) => prefetches,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut slot = match self.ufo.write() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if self.detached {
+            return;
+        }
+        // Nothing left to do if the handle was already freed explicitly
+        if let Some(ufo) = slot.take() {
+            self.prefetcher.unregister(&ufo);
+            let in_flight = self.frees.start();
+            self.count_out();
+            if let Ok(mut ufo) = ufo.write() {
+                // may have failed if the core is shutdown
+                if let Ok(waiter) = ufo.free() {
+                    self.frees.reap(in_flight, move || waiter.wait());
+                }
+            }
+        }
+    }
+}
+
+/// A typed view of a ufo's body as a `[T]`
+///
+/// Keeps the pointer arithmetic that would otherwise be repeated at every `body_ptr` call site
+/// in one place. The handle is owned by the view, so slices borrowed from it cannot outlive the
+/// object. `LazyVec` is one of these over a generator.
+pub struct UfoVec<T> {
+    // the handle is dropped (and the object freed) before the core reference it depends on
+    handle: UfoHandle,
+    _core: UfoCore,
+    body: *mut T,
+    len: usize,
+}
+
+impl<T: Pod> UfoVec<T> {
+    /// Allocates an object, `params.stride` has to be the size of `T`
+    pub fn new(core: &UfoCore, params: UfoObjectParams) -> Result<Self, UfoErr> {
+        if params.stride != size_of::<T>() {
+            return Err(UfoErr::ElementSize {
+                stride: params.stride,
+                element_size: size_of::<T>(),
+            });
+        }
+
+        Ok(Self::from_handle(core, core.new_ufo(params)?)?)
+    }
+
+    // Wraps a handle whose stride is already known to match `T`
+    pub(crate) fn from_handle(core: &UfoCore, handle: UfoHandle) -> Result<Self, UfoInternalErr> {
+        let body = handle.body_ptr()?.cast();
+        let len = handle.info().element_ct;
+        Ok(UfoVec {
+            handle,
+            _core: core.clone(),
+            body,
+            len,
+        })
+    }
+
+    pub fn info(&self) -> &UfoInfo {
+        self.handle.info()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.body, self.len) }
+    }
+
+    /// Panics if the object is read only
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        assert!(!self.handle.is_read_only(), "cannot write to a read only object");
+        unsafe { std::slice::from_raw_parts_mut(self.body, self.len) }
+    }
+
+    /// The header read as an `H`, panics if `H` is larger than the header or misaligned for it
+    pub fn header<H: Pod>(&self) -> Result<&H, UfoInternalErr> {
+        let header = self.header_ptr::<H>()?;
+        Ok(unsafe { &*header })
+    }
+
+    pub fn header_mut<H: Pod>(&mut self) -> Result<&mut H, UfoInternalErr> {
+        let header = self.header_ptr::<H>()?;
+        Ok(unsafe { &mut *header })
+    }
+
+    fn header_ptr<H: Pod>(&self) -> Result<*mut H, UfoInternalErr> {
+        assert!(
+            size_of::<H>() <= self.info().header_size,
+            "{} byte header type does not fit in {} bytes",
+            size_of::<H>(),
+            self.info().header_size
+        );
+        let header = self.handle.header_ptr()?.cast::<H>();
+        assert_eq!(
+            0,
+            header as usize % std::mem::align_of::<H>(),
+            "header is not aligned for the header type"
+        );
+        Ok(header)
+    }
+
+    /// Discards all writes, the body is populated again on next access
+    pub fn reset(&mut self) -> Result<(), UfoInternalErr> {
+        self.handle.reset()
+    }
+
+    pub(crate) fn handle(&self) -> &UfoHandle {
+        &self.handle
+    }
+
+    /// Gives up the typed view
+    pub fn into_handle(self) -> UfoHandle {
+        self.handle
+    }
+}
+
+impl<T: Pod> std::ops::Deref for UfoVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T: Pod> std::ops::DerefMut for UfoVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use num::Integer;
+    use std::{
+        convert::{TryFrom, TryInto},
+        fmt::Debug,
+        mem::size_of,
+    };
+    use test_util::{sequence_params, test_core};
+    use ufo_core::{UfoAllocateErr, UfoCoreConfig};
+
+    #[test]
+    fn core_starts() {
+        let config = UfoCoreConfig {
+            writeback_temp_path: "/tmp".to_string(),
+            high_watermark: 1024 * 1024 * 1024,
+            low_watermark: 512 * 1024 * 1024,
+        };
+        let core = UfoCore::new_ufo_core(config).expect("error getting core");
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        std::mem::drop(core);
+    }
+
+    #[test]
+    fn page_size_is_sane() {
+        let core = test_core();
+
+        assert!(core.page_size() >= 4096);
+        assert!(core.page_size().is_power_of_two());
+    }
+
+    fn basic_test_object<T>(
+        header_size: usize,
+        body_size: usize,
+        min_load: usize,
+        read_only: bool,
+    ) -> Result<(UfoCore, UfoHandle), UfoAllocateErr>
+    where
+        T: Sized + Integer + TryFrom<usize>,
+        <T as TryFrom<usize>>::Error: Debug,
+    {
+        let config = UfoCoreConfig {
+            writeback_temp_path: "/tmp".to_string(),
+            high_watermark: 1024 * 1024 * 1024,
+            low_watermark: 512 * 1024 * 1024,
+        };
+        let core = UfoCore::new_ufo_core(config).expect("error getting core");
+
+        let ufo_params = UfoObjectParams {
+            header_size,
+            stride: size_of::<T>(),
+            min_load_ct: Some(min_load),
+            read_only,
+            element_ct: body_size,
+            populate: Box::new(|start, end, fill| {
+                let slice = unsafe {
+                    std::slice::from_raw_parts_mut::<T>(fill.cast(), size_of::<T>() * (end - start))
+                };
+                for idx in start..end {
+                    slice[idx - start] = idx.try_into().unwrap();
+                }
+
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+
+        let o = core.new_ufo(ufo_params)?;
+
+        Ok((core, o))
+    }
+
+    #[test]
+    fn event_receiver_sees_events() -> Result<(), UfoErr> {
+        let (core, o) = basic_test_object::<u32>(0, 1000, 4096, false)?;
+        let rx = core.event_receiver();
+
+        unsafe { assert_eq!(*o.body_ptr()?.cast::<u32>().add(999), 999) };
+        o.free()?;
+        std::mem::drop(core);
+
+        assert!(rx.try_iter().count() > 0);
+        Ok(())
+    }
+
+    // Elements of N u32s, so strides that are not powers of two and straddle page boundaries
+    fn strided_test_object<const N: usize>(
+        element_ct: usize,
+        min_load: usize,
+    ) -> Result<(UfoCore, UfoHandle), UfoAllocateErr> {
+        let core = test_core();
+
+        let ufo_params = UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<[u32; N]>(),
+            min_load_ct: Some(min_load),
+            read_only: false,
+            element_ct,
+            populate: Box::new(|start, end, fill| {
+                let slice = unsafe {
+                    std::slice::from_raw_parts_mut::<[u32; N]>(fill.cast(), end - start)
+                };
+                for idx in start..end {
+                    slice[idx - start] = strided_element(idx);
+                }
+
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+
+        let o = core.new_ufo(ufo_params)?;
+
+        Ok((core, o))
+    }
+
+    fn strided_element<const N: usize>(idx: usize) -> [u32; N] {
+        let mut e = [0; N];
+        for (k, v) in e.iter_mut().enumerate() {
+            *v = (idx * N + k) as u32;
+        }
+        e
+    }
+
+    fn check_strided<const N: usize>() -> anyhow::Result<()> {
+        let ct = 1000 * 1000;
+        // a min load that does not fill a whole number of pages for any of the strides
+        let (core, o) = strided_test_object::<N>(ct, 1000)?;
+
+        let arr = unsafe {
+            std::slice::from_raw_parts_mut(o.body_ptr().unwrap().cast::<[u32; N]>(), ct)
+        };
+
+        // walk backwards so chunks are faulted starting from their last element
+        for idx in (0..ct).rev() {
+            if arr[idx] != strided_element::<N>(idx) {
+                anyhow::bail!("{:?} != {:?} @ {}", arr[idx], strided_element::<N>(idx), idx);
+            }
+        }
+
+        // elements spanning a page boundary must be written and read back whole
+        let page = 4096;
+        let stride = size_of::<[u32; N]>();
+        for boundary in (page..ct * stride).step_by(page * 97) {
+            let idx = boundary / stride;
+            arr[idx] = [7; N];
+            assert_eq!([7; N], arr[idx]);
+            assert_eq!(strided_element::<N>(idx + 1), arr[idx + 1]);
+        }
+
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn stride_12() -> anyhow::Result<()> {
+        check_strided::<3>()
+    }
+
+    #[test]
+    fn stride_24() -> anyhow::Result<()> {
+        check_strided::<6>()
+    }
+
+    #[test]
+    fn stride_40() -> anyhow::Result<()> {
+        check_strided::<10>()
+    }
+
+    #[test]
+    fn it_works() -> Result<(), UfoAllocateErr> {
+        let (core, o) = basic_test_object::<u32>(0, 1000 * 1000, 4096, false)?;
+
+        let arr = unsafe {
+            std::slice::from_raw_parts_mut(o.body_ptr().unwrap().cast::<u32>(), 1000 * 1000)
+        };
+
+        for x in 0..1000 * 1000 {
+            assert_eq!(x as u32, arr[x]);
+        }
+
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn free_ufo() -> Result<(), UfoErr> {
+        // use stderrlog;
+        // stderrlog::new()
+        //     // .module("ufo_core")
+        //     .verbosity(4)
+        //     .timestamp(stderrlog::Timestamp::Microsecond)
+        //     .init()
+        //     .unwrap();
+
+        let (core, o) = basic_test_object::<u32>(0, 1000 * 1000, 4096, false)?;
+
+        o.free()?;
+
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn use_after_free() -> Result<(), UfoErr> {
+        let (core, o) = basic_test_object::<u32>(1, 1000 * 1000, 4096, false)?;
+
+        o.free()?;
+
+        assert!(matches!(o.header_ptr(), Err(UfoInternalErr::UfoNotFound)));
+        assert!(matches!(o.body_ptr(), Err(UfoInternalErr::UfoNotFound)));
+        assert!(matches!(o.reset(), Err(UfoInternalErr::UfoNotFound)));
+        assert!(matches!(o.free(), Err(UfoErr::Core(UfoInternalErr::UfoNotFound))));
+
+        // dropping an already freed handle is a no-op
+        std::mem::drop(o);
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_ufo() -> Result<(), UfoAllocateErr> {
+        let core = test_core();
+
+        let params = sequence_params(1000);
+
+        let sum = core.with_ufo(params, |o| {
+            let arr =
+                unsafe { std::slice::from_raw_parts(o.body_ptr().unwrap().cast::<u32>(), 1000) };
+            arr.iter().sum::<u32>()
+        })?;
+        assert_eq!(999 * 1000 / 2, sum);
+
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn counts_live_ufos() -> Result<(), UfoErr> {
+        let (core, a) = basic_test_object::<u32>(0, 1000, 4096, false)?;
+        assert_eq!(1, core.ufo_count());
+
+        let b = core.with_ufo(sequence_params(1000), |_| core.ufo_count())?;
+        assert_eq!(2, b);
+        assert_eq!(1, core.ufo_count());
+
+        a.free()?;
+        assert_eq!(0, core.ufo_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn foreign_ownership() -> Result<(), UfoErr> {
+        let (core, a) = basic_test_object::<u32>(0, 1000, 4096, false)?;
+        let body = a.body_ptr()?.cast::<u32>();
+        let ptr = a.into_raw().as_ptr();
+        assert_eq!(1, core.ufo_count());
+
+        let a = unsafe { core.handle_from_raw(UfoHandleRaw::from_ptr(ptr)) };
+        assert_eq!(body, a.body_ptr()?.cast());
+        assert_eq!(999, unsafe { *body.add(999) });
+
+        let mut b = core.new_ufo(sequence_params(1000))?;
+        b.detach();
+        b.free()?;
+        assert_eq!(1, core.ufo_count());
+
+        let mut c = core.new_ufo(sequence_params(1000))?;
+        c.detach();
+        drop(c);
+        assert_eq!(2, core.ufo_count());
+
+        a.free()?;
+        Ok(())
+    }
+
+    #[test]
+    fn sealed_ufo_still_reads() -> anyhow::Result<()> {
+        let ct = 1000 * 1000;
+        let (core, o) = basic_test_object::<u32>(0, ct, 4096, false)?;
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), ct) };
+        // some pages resident before sealing, the rest populated afterwards
+        assert_eq!(10, arr[10]);
+
+        o.seal()?;
+        assert!(o.is_sealed());
+        for (i, x) in arr.iter().enumerate() {
+            if i as u32 != *x {
+                anyhow::bail!("{} != {}", i, x);
+            }
+        }
+
+        o.reset()?;
+        assert_eq!(ct as u32 - 1, arr[ct - 1]);
+
+        o.free()?;
+        assert!(matches!(o.seal(), Err(UfoErr::Core(UfoInternalErr::UfoNotFound))));
+
+        std::mem::drop(core);
+        Ok(())
+    }
+
+    #[test]
+    fn deferred_allocation() -> Result<(), UfoInternalErr> {
+        let core = test_core();
+
+        let (a, waiter) = core.new_ufo_deferred(sequence_params(1000 * 1000));
+        waiter.wait()?;
+        let arr = unsafe { std::slice::from_raw_parts(a.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(1234, arr[1234]);
+
+        // touching the handle before the waiter completes blocks until the object is ready
+        let (b, _waiter) = core.new_ufo_deferred(sequence_params(1000));
+        let arr = unsafe { std::slice::from_raw_parts(b.body_ptr()?.cast::<u32>(), 1000) };
+        assert_eq!(999, arr[999]);
+        assert_eq!(2, core.ufo_count());
+
+        // dropping an unresolved handle still frees the object
+        std::mem::drop(core.new_ufo_deferred(sequence_params(1000)));
+        assert_eq!(2, core.ufo_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn free_many() -> Result<(), UfoErr> {
+        let core = test_core();
+
+        let mut handles = Vec::new();
+        for _ in 0..16 {
+            let o = core.new_ufo(sequence_params(100 * 1000))?;
+            unsafe { assert_eq!(7, *o.body_ptr()?.cast::<u32>().add(7)) };
+            handles.push(o);
+        }
+        assert_eq!(16, core.ufo_count());
+
+        let freed_early = core.new_ufo(sequence_params(1000))?;
+        freed_early.free()?;
+        handles.push(freed_early);
+
+        let results = core.free_all(handles).wait();
+        assert_eq!(17, results.len());
+        assert!(results[..16].iter().all(Result::is_ok));
+        assert!(matches!(results[16], Err(UfoErr::Core(UfoInternalErr::UfoNotFound))));
+        assert_eq!(0, core.ufo_count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn measures_populate_throughput() -> Result<(), UfoInternalErr> {
+        let (core, o) = basic_test_object::<u64>(0, 1000 * 1000, 4096, false)?;
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u64>(), 1000 * 1000) };
+        assert_eq!(1000 * 1000 - 1, arr.iter().copied().max().unwrap());
+
+        assert!(o.populate_throughput_bps() > 0.0);
+        assert!(core.populate_throughput_bps() > 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn debug_dump_elides_long_ranges() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(5, arr[5]);
+
+        let mut out = Vec::new();
+        o.debug_dump::<u32, _>(0..3, &mut out)?;
+        assert_eq!("0: 0\n1: 1\n2: 2\n", String::from_utf8(out).unwrap());
+
+        let mut out = Vec::new();
+        o.debug_dump::<u32, _>(0..1000, &mut out)?;
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(DEBUG_DUMP_LIMIT + 1, lines.len());
+        assert_eq!("... 936 elements elided ...", lines[DEBUG_DUMP_LIMIT / 2]);
+        assert_eq!("999: 999", lines[DEBUG_DUMP_LIMIT]);
+
+        assert!(matches!(
+            o.debug_dump::<u64, _>(0..3, &mut Vec::new()),
+            Err(UfoErr::ElementSize { stride: 4, element_size: 8 })
+        ));
+        assert!(matches!(
+            o.debug_dump::<u32, _>(999 * 1000..1000 * 1000 + 1, &mut Vec::new()),
+            Err(UfoErr::OutOfRange { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn copy_between_objects() -> Result<(), UfoErr> {
+        let core = test_core();
+        let src = core.new_ufo(sequence_params(1000 * 1000))?;
+        let dst = core.new_ufo(UfoObjectParams {
+            populate: Box::new(|_, _, _| Ok(())),
+            ..sequence_params(1000 * 1000)
+        })?;
+
+        core.copy_ufo_range(&src, 1000, &dst, 0, 500 * 1000)?;
+        let arr = unsafe { std::slice::from_raw_parts(dst.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        for (i, x) in arr[..500 * 1000].iter().enumerate() {
+            assert_eq!((i + 1000) as u32, *x);
+        }
+        assert_eq!(0, arr[500 * 1000]);
+
+        // shifting within one object must not clobber elements before they are copied
+        core.copy_ufo_range(&src, 0, &src, 10, 900 * 1000)?;
+        let arr = unsafe { std::slice::from_raw_parts(src.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(9, arr[9]);
+        for (i, x) in arr[10..900 * 1000 + 10].iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+
+        let wide = core.new_ufo(UfoObjectParams {
+            stride: 8,
+            ..sequence_params(1000)
+        })?;
+        assert!(matches!(
+            core.copy_ufo_range(&src, 0, &wide, 0, 10),
+            Err(UfoErr::StrideMismatch { src: 4, dst: 8 })
+        ));
+
+        assert!(matches!(
+            core.copy_ufo_range(&src, 999 * 1000, &dst, 0, 2000),
+            Err(UfoErr::OutOfRange { start: 999000, count: 2000, len: 1000000 })
+        ));
+        assert!(matches!(
+            core.copy_ufo_range(&src, 0, &dst, usize::MAX, 2),
+            Err(UfoErr::OutOfRange { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn raw_bytes() -> Result<(), UfoErr> {
+        let core = test_core();
+        let mut o = core.new_ufo(sequence_params(1000))?;
+
+        let bytes = o.as_bytes()?;
+        assert_eq!(4000, bytes.len());
+        assert_eq!(&7u32.to_ne_bytes(), &bytes[28..32]);
+        drop(bytes);
+
+        o.as_bytes_mut()?[28..32].copy_from_slice(&42u32.to_ne_bytes());
+        assert_eq!(42, o.as_slice::<u32>()?[7]);
+
+        o.free()?;
+        assert!(matches!(
+            o.as_bytes(),
+            Err(UfoErr::Core(UfoInternalErr::UfoNotFound))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_faults_populate_once() -> Result<(), UfoErr> {
+        let core = test_core();
+        let chunk = 4096;
+        let calls: Arc<Vec<AtomicUsize>> = Arc::new((0..16).map(|_| AtomicUsize::new(0)).collect());
+        let counts = calls.clone();
+        let o = core.new_ufo(UfoObjectParams {
+            min_load_ct: Some(chunk),
+            populate: Box::new(move |start, end, fill| {
+                for c in start / chunk..end.div_ceil(chunk) {
+                    counts[c].fetch_add(1, Ordering::AcqRel);
+                }
+                let slice =
+                    unsafe { std::slice::from_raw_parts_mut::<u32>(fill.cast(), end - start) };
+                for idx in start..end {
+                    slice[idx - start] = idx as u32;
+                }
+                Ok(())
+            }),
+            ..sequence_params(chunk * 16)
+        })?;
+
+        let body = o.body_ptr()? as usize;
+        let barrier = Arc::new(std::sync::Barrier::new(32));
+        let threads: Vec<_> = (0..32)
+            .map(|t| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    // every thread hits the same chunk at a slightly different offset
+                    let idx = 3 * chunk + t * 17;
+                    unsafe { std::ptr::read_volatile((body as *const u32).add(idx)) }
+                })
+            })
+            .collect();
+        for (t, thread) in threads.into_iter().enumerate() {
+            assert_eq!((3 * chunk + t * 17) as u32, thread.join().unwrap());
+        }
+
+        assert_eq!(1, calls[3].load(Ordering::Acquire));
+        Ok(())
+    }
+
+    #[test]
+    fn iterates_resident_elements() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(123, arr[123]);
+
+        let resident: Vec<_> = o.iter_resident::<u32>()?.collect();
+        assert!(resident.iter().any(|(i, _)| *i == 123));
+        for (i, x) in resident {
+            assert_eq!(i as u32, x);
+            assert!(o.is_resident(i)?);
+        }
+        assert!(matches!(
+            o.iter_resident::<u64>(),
+            Err(UfoErr::ElementSize { stride: 4, element_size: 8 })
+        ));
+
+        // the iterator holds the object, a free is refused until it is dropped
+        let mut resident = o.iter_resident::<u32>()?;
+        assert!(matches!(o.free(), Err(UfoErr::InUse)));
+        assert_eq!(Some((123, 123)), resident.find(|(i, _)| *i == 123));
+        drop(resident);
+        o.free()?;
+        Ok(())
+    }
+
+    #[test]
+    fn reset_regenerates_from_header() -> Result<(), UfoErr> {
+        let core = test_core();
+        // populate scales each index by the u32 stored in the header
+        let header = Arc::new(AtomicUsize::new(0));
+        let header_addr = header.clone();
+        let o = core.new_ufo(UfoObjectParams {
+            header_size: size_of::<u32>(),
+            populate: Box::new(move |start, end, fill| {
+                // the header is only meaningful once the first reset_with_header wrote it
+                let factor = match header_addr.load(Ordering::Acquire) {
+                    0 => 0,
+                    addr => unsafe { *(addr as *const u32) },
+                };
+                let slice =
+                    unsafe { std::slice::from_raw_parts_mut::<u32>(fill.cast(), end - start) };
+                for idx in start..end {
+                    slice[idx - start] = idx as u32 * factor;
+                }
+                Ok(())
+            }),
+            ..sequence_params(1000 * 1000)
+        })?;
+        header.store(o.header_ptr()? as usize, Ordering::Release);
+
+        o.reset_with_header(&2u32.to_ne_bytes())?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(6, arr[3]);
+
+        o.reset_with_header(&5u32.to_ne_bytes())?;
+        assert_eq!(15, arr[3]);
+        assert_eq!(5 * 999 * 1000, arr[999 * 1000]);
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_populate_verifies() -> Result<(), UfoErr> {
+        let core = test_core();
+        core.verify_populate_determinism(true);
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        assert_eq!(cfg!(debug_assertions), o.checksums.is_some());
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(77, arr[77]);
+        o.reset()?;
+        assert_eq!(78, arr[78]);
+        assert!(o.nondeterministic_ranges().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn graduates_to_box() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        let boxed = o.into_boxed_slice::<u32>()?;
+
+        assert_eq!(0, core.ufo_count());
+        let wide = core.new_ufo(sequence_params(1000))?;
+        assert!(matches!(
+            wide.into_boxed_slice::<u64>(),
+            Err(UfoErr::ElementSize { stride: 4, element_size: 8 })
+        ));
+        assert_eq!(1000 * 1000, boxed.len());
+        for (i, x) in boxed.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn maps_in_place() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        o.map_in_place(|x: u32| x * 2)?;
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        for (i, x) in arr.iter().enumerate() {
+            assert_eq!(i as u32 * 2, *x);
+        }
+
+        let ro = core.new_ufo(UfoObjectParams {
+            read_only: true,
+            ..sequence_params(1000)
+        })?;
+        assert!(matches!(ro.map_in_place(|x: u32| x), Err(UfoErr::ReadOnly)));
+        assert!(matches!(
+            o.map_in_place(|x: u64| x),
+            Err(UfoErr::ElementSize { stride: 4, element_size: 8 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_rejects_writes() -> Result<(), UfoErr> {
+        let core = test_core();
+        let mut o = core.new_ufo(UfoObjectParams {
+            read_only: true,
+            ..sequence_params(1000)
+        })?;
+        assert!(o.is_read_only());
+        assert!(matches!(o.as_bytes_mut(), Err(UfoErr::ReadOnly)));
+        assert!(matches!(o.as_mut_slice::<u32>(), Err(UfoErr::ReadOnly)));
+        let body = o.body_ptr()?.cast::<u32>();
+
+        // the write has to happen in a child, it kills whoever makes it
+        match unsafe { libc::fork() } {
+            0 => unsafe {
+                body.add(7).write_volatile(1);
+                libc::_exit(0)
+            },
+            child => {
+                let mut status = 0;
+                assert_eq!(child, unsafe { libc::waitpid(child, &mut status, 0) });
+                assert!(libc::WIFSIGNALED(status));
+                assert_eq!(libc::SIGSEGV, libc::WTERMSIG(status));
+            }
+        }
+
+        // the element holds its populated value, not the 1 the child tried to write
+        assert_eq!(7, unsafe { *body.add(7) });
+        o.reset()?;
+        assert_eq!(999, unsafe { *body.add(999) });
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_rejects_copies_and_transactions() -> Result<(), UfoErr> {
+        let core = test_core();
+        let src = core.new_ufo(sequence_params(1000))?;
+        let o = core.new_ufo(UfoObjectParams {
+            read_only: true,
+            ..sequence_params(1000)
+        })?;
+
+        assert!(matches!(
+            core.copy_ufo_range(&src, 0, &o, 0, 10),
+            Err(UfoErr::ReadOnly)
+        ));
+        let txn = o.transaction(|txn: &mut TxnSlice<u32>| {
+            txn.set(0, 1);
+            Ok::<_, UfoErr>(())
+        });
+        assert!(matches!(txn, Err(UfoErr::ReadOnly)));
+
+        // reading from a read only object is still fine
+        core.copy_ufo_range(&o, 5, &src, 0, 10)?;
+        assert_eq!(5, unsafe { *src.body_ptr()?.cast::<u32>() });
+        Ok(())
+    }
+
+    #[test]
+    fn self_test_passes() -> Result<(), UfoErr> {
+        let core = test_core();
+        core.self_test()?;
+        assert_eq!(0, core.ufo_count());
+        Ok(())
+    }
+
+    #[test]
+    fn populate_range_is_clamped() -> Result<(), UfoErr> {
+        let core = test_core();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let o = core.new_ufo(UfoObjectParams {
+            min_load_ct: Some(2 * 1000 * 1000),
+            populate: Box::new(move |start, end, fill| {
+                assert!(end <= 1000, "populate asked for {}..{}", start, end);
+                counted.fetch_add(1, Ordering::AcqRel);
+                let slice =
+                    unsafe { std::slice::from_raw_parts_mut::<u32>(fill.cast(), end - start) };
+                for idx in start..end {
+                    slice[idx - start] = idx as u32;
+                }
+                Ok(())
+            }),
+            ..sequence_params(1000)
+        })?;
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000) };
+        assert_eq!(0, arr[0]);
+        assert_eq!(999, arr[999]);
+        assert_eq!(1, calls.load(Ordering::Acquire));
+        Ok(())
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[test]
+    fn reports_leaked_objects() -> Result<(), UfoErr> {
+        let core = test_core();
+        let kept = core.new_ufo(sequence_params(1234))?;
+        let freed = core.new_ufo(sequence_params(5678))?;
+        freed.free()?;
+
+        let report = core.leaked_objects_report();
+        assert!(report.starts_with("1 live objects\n"));
+        assert!(report.contains("1234 elements of 4 bytes"));
+        assert!(!report.contains("5678 elements"));
+        assert!(!kept.allocation_site().to_string().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn content_hash_ignores_residency() -> Result<(), UfoErr> {
+        let core = test_core();
+        let a = core.new_ufo(sequence_params(1000 * 1000))?;
+        let b = core.new_ufo(sequence_params(1000 * 1000))?;
+
+        // a is read ahead of time, b is hashed straight away
+        let arr = unsafe { std::slice::from_raw_parts(a.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(500, arr[500]);
+        assert_eq!(a.content_hash()?, b.content_hash()?);
+
+        let mut c = core.new_ufo(sequence_params(1000 * 1000))?;
+        c.as_bytes_mut()?[0] = 1;
+        assert_ne!(a.content_hash()?, c.content_hash()?);
+        Ok(())
+    }
+
+    #[test]
+    fn typed_view() -> Result<(), UfoErr> {
+        let core = test_core();
+        let mut v = UfoVec::<u32>::new(
+            &core,
+            UfoObjectParams {
+                header_size: size_of::<u64>(),
+                ..sequence_params(1000 * 1000)
+            },
+        )?;
+
+        assert_eq!(1000 * 1000, v.len());
+        for (i, x) in v.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+        v[10] = 0;
+        assert_eq!(0, v.as_slice()[10]);
+
+        *v.header_mut::<u64>()? = 77;
+        assert_eq!(77, *v.header::<u64>()?);
+
+        v.reset()?;
+        assert_eq!(10, v[10]);
+
+        assert!(matches!(
+            UfoVec::<u64>::new(&core, sequence_params(10)),
+            Err(UfoErr::ElementSize {
+                stride: 4,
+                element_size: 8
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "read only")]
+    fn typed_view_of_read_only_object() {
+        let core = test_core();
+        let params = UfoObjectParams {
+            read_only: true,
+            ..sequence_params(1000)
+        };
+        let mut v = UfoVec::<u32>::new(&core, params).unwrap();
+        assert_eq!(999, v[999]);
+        v[0] = 1;
+    }
+
+    // sequence params whose first populate call fails
+    fn flaky_params(element_ct: usize) -> UfoObjectParams {
+        let failed = AtomicBool::new(false);
+        UfoObjectParams {
+            populate: Box::new(move |start, end, fill| {
+                if !failed.swap(true, Ordering::AcqRel) {
+                    return Err(UfoPopulateError);
+                }
+                let slice =
+                    unsafe { std::slice::from_raw_parts_mut::<u32>(fill.cast(), end - start) };
+                for idx in start..end {
+                    slice[idx - start] = idx as u32 + 1;
+                }
+                Ok(())
+            }),
+            ..sequence_params(element_ct)
+        }
+    }
+
+    #[test]
+    fn populate_failure_zero_fills() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(flaky_params(1000))?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000) };
+
+        assert_eq!(0, arr[0]);
+        assert!(o.is_poisoned());
+        assert_eq!(0, o.failed_ranges()[0].start);
+        o.map_in_place(|x: u32| x)?;
+
+        o.reset()?;
+        assert_eq!(1, arr[0]);
+        assert!(!o.is_poisoned());
+        assert!(o.failed_ranges().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn populate_panic_zero_fills() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(UfoObjectParams {
+            populate: Box::new(|_, _, _| panic!("populate gave up")),
+            ..sequence_params(1000)
+        })?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000) };
+
+        assert_eq!(0, arr[0]);
+        assert!(o.is_poisoned());
+        Ok(())
+    }
+
+    #[test]
+    fn populate_failure_poisons() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo_with_policy(flaky_params(1000), PopulateFailurePolicy::Poison)?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000) };
+
+        assert_eq!(0, arr[0]);
+        assert!(o.is_poisoned());
+        assert!(matches!(o.map_in_place(|x: u32| x), Err(UfoErr::Poisoned)));
+
+        // a dead object stays dead, but can still be freed
+        o.reset()?;
+        assert!(o.is_poisoned());
+        o.free()?;
+        Ok(())
+    }
+
+    #[test]
+    fn prefetch_populates_ahead() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        // residency goes through mincore, nothing in this test reads the body itself
+        let resident = |range: Range<usize>| -> Result<Vec<bool>, UfoErr> {
+            range.step_by(1000).map(|idx| o.is_resident(idx)).collect()
+        };
+        assert!(!resident(0..1000 * 1000)?.contains(&true));
+
+        let queued = o.prefetch(0, 500 * 1000)?.wait();
+        assert!(queued > 0 && queued <= 500 * 1000);
+        assert!(o.stats().chunks_populated > 0);
+        assert!(!resident(0..500 * 1000)?.contains(&false));
+        assert!(!resident(750 * 1000..1000 * 1000)?.contains(&true));
+        // nothing left to bring in the second time around
+        assert_eq!(0, o.prefetch(0, 500 * 1000)?.wait());
+
+        let slow = o.prefetch(0, 1000 * 1000)?;
+        o.free()?;
+        assert_eq!(0, slow.wait());
+        Ok(())
+    }
+
+    #[test]
+    fn prefetch_budget_covers_the_whole_core() -> Result<(), UfoErr> {
+        let page = system_page_size();
+        let core = UfoCore::new_ufo_core(UfoCoreConfig {
+            high_watermark: 64 * page,
+            low_watermark: 32 * page,
+            ..test_util::test_config()
+        })
+        .expect("error getting core");
+        let elements_per_page = page / size_of::<u32>();
+
+        let a = core.new_ufo(sequence_params(48 * elements_per_page))?;
+        for (page, x) in a.as_slice::<u32>()?.iter().step_by(elements_per_page).enumerate() {
+            assert_eq!((page * elements_per_page) as u32, *x);
+        }
+        let b = core.new_ufo(sequence_params(64 * elements_per_page))?;
+        // only the room left by the other object's pages is prefetched
+        let queued = b.prefetch(0, 64 * elements_per_page)?.wait();
+        assert!(queued <= 16 * elements_per_page);
+        Ok(())
+    }
+
+    #[test]
+    fn tracks_object_stats() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        assert_eq!(999 * 1000, arr[999 * 1000]);
+
+        let stats = o.stats();
+        assert!(stats.chunks_populated > 0);
+        assert!(stats.bytes_populated >= 4096);
+        assert!(stats.bytes_populated <= 4 * 1000 * 1000);
+        assert_eq!(0, stats.writebacks);
+        assert_eq!(
+            stats.chunks_populated,
+            stats.populate_time_histogram.iter().sum::<u64>()
+        );
+
+        o.reset_stats();
+        assert_eq!(UfoStats::default(), o.stats());
+        // the core keeps counting what its objects did
+        assert!(core.stats().chunks_populated >= stats.chunks_populated);
+        Ok(())
+    }
+
+    #[test]
+    fn bounded_reset_and_free() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000 * 1000))?;
+        let arr = unsafe { std::slice::from_raw_parts_mut(o.body_ptr()?.cast::<u32>(), 1000 * 1000) };
+        arr[3] = 0;
+
+        o.reset_timeout(Duration::from_secs(10))?;
+        assert_eq!(3, arr[3]);
+
+        o.free_timeout(Duration::from_secs(10))?;
+        assert_eq!(0, core.ufo_count());
+        assert!(o.free_timeout(Duration::from_secs(10)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_of_read_only_object() -> Result<(), UfoErr> {
+        let core = test_core();
+        let parent = core.new_ufo(UfoObjectParams {
+            header_size: 8,
+            read_only: true,
+            ..sequence_params(100 * 1000)
+        })?;
+        assert!(matches!(
+            core.snapshot(&core.new_ufo(sequence_params(10))?),
+            Err(UfoErr::Writable)
+        ));
+
+        let snapshot = core.snapshot(&parent)?;
+        let arr = unsafe {
+            std::slice::from_raw_parts_mut(snapshot.body_ptr()?.cast::<u32>(), 100 * 1000)
+        };
+        arr[7] = 1;
+        assert_eq!(7, unsafe { *parent.body_ptr()?.cast::<u32>().add(7) });
+
+        parent.free()?;
+        for (i, x) in arr.iter().enumerate().skip(8) {
+            assert_eq!(i as u32, *x);
+        }
+        assert_eq!(1, arr[7]);
+        Ok(())
+    }
+
+    #[test]
+    fn begin_reset_and_free() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000))?;
+        let arr = unsafe { std::slice::from_raw_parts_mut(o.body_ptr()?.cast::<u32>(), 1000) };
+        arr[0] = 7;
+
+        let reset = o.reset_begin()?;
+        reset.wait();
+        assert_eq!(0, arr[0]);
+
+        let free = o.free_begin()?;
+        assert_eq!(0, core.ufo_count());
+        while !free.is_done() {
+            std::thread::yield_now();
+        }
+        free.wait();
+        Ok(())
+    }
+
+    #[test]
+    fn shutdown_refuses_live_objects() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(1000))?;
+
+        assert!(matches!(
+            core.clone().shutdown(),
+            Err(UfoErr::HandlesOutstanding(1))
+        ));
+        o.free()?;
+        core.shutdown()
+    }
+
+    #[test]
+    fn shutdown_closes_every_clone() -> Result<(), UfoErr> {
+        let core = test_core();
+        let other = core.clone();
+        // nothing waits for the free started by the drop, shutdown has to
+        let dropped = core.new_ufo(sequence_params(1000 * 1000))?;
+        assert_eq!(7, dropped.as_slice::<u32>()?[7]);
+        drop(dropped);
+
+        core.shutdown()?;
+        assert!(matches!(
+            other.new_ufo(sequence_params(1000)),
+            Err(UfoAllocateErr::MessageSendError)
+        ));
+        let (deferred, allocated) = other.new_ufo_deferred(sequence_params(1000));
+        assert!(matches!(allocated.wait(), Err(UfoAllocateErr::MessageSendError)));
+        drop(deferred);
+        assert_eq!(0, other.ufo_count());
         Ok(())
     }
 
@@ -216,10 +2460,7 @@ mod tests {
         unsafe { assert_eq!(*o.header_ptr().unwrap().cast::<u32>(), 0) };
 
         let arr = unsafe {
-            std::slice::from_raw_parts_mut(
-                o.body_ptr().unwrap().cast::<u32>(),
-                size_of::<u32>() * 1000 * 1000,
-            )
+            std::slice::from_raw_parts_mut(o.body_ptr().unwrap().cast::<u32>(), ct)
         };
 
         for x in 1..=ct {
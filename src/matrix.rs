@@ -0,0 +1,260 @@
+use std::{marker::PhantomData, mem::size_of, ops::Range};
+
+use bytemuck::Pod;
+use ufo_core::{UfoObjectParams, UfoPopulateError};
+
+use crate::{UfoCore, UfoErr, UfoHandle, UfoSlice};
+
+#[derive(Clone, Copy)]
+struct Tiling {
+    rows: usize,
+    cols: usize,
+    tile_rows: usize,
+    tile_cols: usize,
+}
+
+impl Tiling {
+    fn tile_len(&self) -> usize {
+        self.tile_rows * self.tile_cols
+    }
+
+    fn tiles_across(&self) -> usize {
+        self.cols.div_ceil(self.tile_cols)
+    }
+
+    fn tiles_down(&self) -> usize {
+        self.rows.div_ceil(self.tile_rows)
+    }
+
+    // The rows and columns of the matrix a tile covers, clipped at the edges
+    fn extent(&self, tile: usize) -> (Range<usize>, Range<usize>) {
+        let row = (tile / self.tiles_across()) * self.tile_rows;
+        let col = (tile % self.tiles_across()) * self.tile_cols;
+        (
+            row..(row + self.tile_rows).min(self.rows),
+            col..(col + self.tile_cols).min(self.cols),
+        )
+    }
+
+    // Where element (row, col) sits in the body
+    fn offset(&self, row: usize, col: usize) -> usize {
+        let tile = (row / self.tile_rows) * self.tiles_across() + col / self.tile_cols;
+        tile * self.tile_len() + (row % self.tile_rows) * self.tile_cols + col % self.tile_cols
+    }
+}
+
+/// A `rows` by `cols` matrix populated one tile of `tile_rows` by `tile_cols` at a time
+///
+/// The body holds whole tiles, row major within a tile and tiles row major across the matrix,
+/// so a tile is contiguous while rows and column blocks are gathered from several tiles. Tiles
+/// on the right and bottom edges are padded to full size with `T::default()`.
+pub struct UfoMatrix<T> {
+    // the handle is dropped (and the object freed) before the core reference it depends on
+    handle: UfoHandle,
+    _core: UfoCore,
+    tiling: Tiling,
+    _element: PhantomData<T>,
+}
+
+impl<T> UfoMatrix<T>
+where
+    T: Pod + Default,
+{
+    /// Allocates the matrix, `populate(rows, cols, tile)` fills in one tile at a time
+    ///
+    /// `tile` is row major with `tile_cols` elements per row, element `(r, c)` goes at
+    /// `(r - rows.start) * tile_cols + (c - cols.start)`. Edge tiles only cover part of it, the
+    /// rest is already set to `T::default()`.
+    pub fn new<F>(
+        core: &UfoCore,
+        rows: usize,
+        cols: usize,
+        tile_rows: usize,
+        tile_cols: usize,
+        populate: F,
+    ) -> Result<Self, UfoErr>
+    where
+        F: Fn(Range<usize>, Range<usize>, &mut [T]) -> Result<(), UfoPopulateError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        assert!(
+            size_of::<T>() > 0,
+            "UfoMatrix does not support zero sized elements"
+        );
+        assert!(tile_rows > 0 && tile_cols > 0, "tiles must not be empty");
+        let tiling = Tiling {
+            rows,
+            cols,
+            tile_rows,
+            tile_cols,
+        };
+        let tile_len = tiling.tile_len();
+
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<T>(),
+            min_load_ct: Some(tile_len),
+            read_only: false,
+            element_ct: tiling.tiles_down() * tiling.tiles_across() * tile_len,
+            populate: Box::new(move |start, end, fill| {
+                let fill = unsafe { std::slice::from_raw_parts_mut(fill.cast::<T>(), end - start) };
+                let mut scratch = Vec::new();
+                for tile in start / tile_len..end.div_ceil(tile_len) {
+                    let first = tile * tile_len;
+                    let (rows, cols) = tiling.extent(tile);
+                    // tiles straddling the edge of the core's chunk are built aside and copied
+                    let covered = first.max(start)..(first + tile_len).min(end);
+                    let whole = covered.len() == tile_len;
+                    let buf = if whole {
+                        &mut fill[first - start..][..tile_len]
+                    } else {
+                        scratch.resize(tile_len, T::default());
+                        &mut scratch[..]
+                    };
+                    if rows.len() < tile_rows || cols.len() < tile_cols {
+                        buf.fill(T::default());
+                    }
+                    populate(rows, cols, buf)?;
+                    if !whole {
+                        fill[covered.start - start..covered.end - start]
+                            .copy_from_slice(&scratch[covered.start - first..covered.end - first]);
+                    }
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+
+        Ok(UfoMatrix {
+            handle: core.new_ufo(params)?,
+            _core: core.clone(),
+            tiling,
+            _element: PhantomData,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.tiling.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.tiling.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Result<T, UfoErr> {
+        self.check(row, col);
+        Ok(self.handle.as_slice::<T>()?[self.tiling.offset(row, col)])
+    }
+
+    /// Copies out row `row`
+    pub fn row(&self, row: usize) -> Result<Vec<T>, UfoErr> {
+        self.check(row, 0);
+        let body = self.handle.as_slice::<T>()?;
+        let mut out = Vec::with_capacity(self.tiling.cols);
+        for col in (0..self.tiling.cols).step_by(self.tiling.tile_cols) {
+            let start = self.tiling.offset(row, col);
+            let width = self.tiling.tile_cols.min(self.tiling.cols - col);
+            out.extend_from_slice(&body[start..start + width]);
+        }
+        Ok(out)
+    }
+
+    /// Copies out the columns of tile column `block`, row major with the block's width per row
+    pub fn col_block(&self, block: usize) -> Result<Vec<T>, UfoErr> {
+        assert!(
+            block < self.tiling.tiles_across(),
+            "column block {} out of bounds for {} blocks",
+            block,
+            self.tiling.tiles_across()
+        );
+        let col = block * self.tiling.tile_cols;
+        let width = self.tiling.tile_cols.min(self.tiling.cols - col);
+        let body = self.handle.as_slice::<T>()?;
+        let mut out = Vec::with_capacity(self.tiling.rows * width);
+        for row in 0..self.tiling.rows {
+            let start = self.tiling.offset(row, col);
+            out.extend_from_slice(&body[start..start + width]);
+        }
+        Ok(out)
+    }
+
+    /// Views tile `(tile_row, tile_col)` in place, padding included
+    pub fn tile(&self, tile_row: usize, tile_col: usize) -> Result<UfoSlice<'_, T>, UfoErr> {
+        assert!(
+            tile_row < self.tiling.tiles_down() && tile_col < self.tiling.tiles_across(),
+            "tile ({}, {}) out of bounds for {} by {} tiles",
+            tile_row,
+            tile_col,
+            self.tiling.tiles_down(),
+            self.tiling.tiles_across()
+        );
+        let tile_len = self.tiling.tile_len();
+        let first = (tile_row * self.tiling.tiles_across() + tile_col) * tile_len;
+        Ok(self.handle.as_slice::<T>()?.narrow(first..first + tile_len))
+    }
+
+    fn check(&self, row: usize, col: usize) {
+        assert!(
+            row < self.tiling.rows && col < self.tiling.cols,
+            "({}, {}) out of bounds for a {} by {} matrix",
+            row,
+            col,
+            self.tiling.rows,
+            self.tiling.cols
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+
+    #[test]
+    fn tiled_access() -> Result<(), UfoErr> {
+        let core = test_core();
+        let m = UfoMatrix::<u32>::new(&core, 1000, 70, 64, 32, |rows, cols, tile| {
+            for r in rows.clone() {
+                for c in cols.clone() {
+                    tile[(r - rows.start) * 32 + (c - cols.start)] = (r * 100 + c) as u32;
+                }
+            }
+            Ok(())
+        })?;
+
+        assert_eq!(99969, m.get(999, 69)?);
+        assert_eq!((0..70).map(|c| 51200 + c).collect::<Vec<_>>(), m.row(512)?);
+
+        // the last block is only 6 columns wide
+        let block = m.col_block(2)?;
+        assert_eq!(1000 * 6, block.len());
+        assert_eq!(
+            &[70064, 70065, 70066, 70067, 70068, 70069],
+            &block[6 * 700..6 * 701]
+        );
+
+        // rows 960..1000 and columns 64..70, the rest is padding
+        let tile = m.tile(15, 2)?;
+        assert_eq!(64 * 32, tile.len());
+        assert_eq!(96064, tile[0]);
+        assert_eq!(0, tile[6]);
+        assert!(tile[40 * 32..].iter().all(|&x| x == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn matrix_outlives_core_clone() -> Result<(), UfoErr> {
+        let m = UfoMatrix::<u16>::new(&test_core(), 10, 10, 4, 4, |rows, cols, tile| {
+            for r in rows.clone() {
+                for c in cols.clone() {
+                    tile[(r - rows.start) * 4 + (c - cols.start)] = (r * 10 + c) as u16;
+                }
+            }
+            Ok(())
+        })?;
+        assert_eq!(99, m.get(9, 9)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use crossbeam::channel::{self, Sender};
+
+type Wait = Box<dyn FnOnce() + Send>;
+
+/// Frees the core has accepted but not finished, so `UfoCore::shutdown` can wait for their
+/// writebacks to land
+pub(crate) struct PendingFrees {
+    in_flight: Mutex<usize>,
+    idle: Condvar,
+    // waits for the frees started by dropping a handle, nobody else is left to wait for those
+    reaper: Sender<Wait>,
+}
+
+/// One free counted as in flight until this is dropped
+pub(crate) struct InFlight(Arc<PendingFrees>);
+
+impl PendingFrees {
+    pub(crate) fn new() -> io::Result<Arc<Self>> {
+        let (reaper, waits) = channel::unbounded::<Wait>();
+        std::thread::Builder::new()
+            .name("ufo-free".to_string())
+            .spawn(move || waits.iter().for_each(|wait| wait()))?;
+        Ok(Arc::new(PendingFrees {
+            in_flight: Mutex::new(0),
+            idle: Condvar::new(),
+            reaper,
+        }))
+    }
+
+    pub(crate) fn start(self: &Arc<Self>) -> InFlight {
+        *self.in_flight.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        InFlight(self.clone())
+    }
+
+    /// Waits for a free in the background instead of on the caller's thread
+    pub(crate) fn reap<F>(&self, in_flight: InFlight, wait: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // if the reaper is gone the free is simply no longer counted
+        let _ = self.reaper.send(Box::new(move || {
+            wait();
+            drop(in_flight);
+        }));
+    }
+
+    /// Blocks until every free started so far has finished
+    pub(crate) fn wait_idle(&self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_flight > 0 {
+            in_flight = self.idle.wait(in_flight).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let mut in_flight = self.0.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        *in_flight -= 1;
+        if *in_flight == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
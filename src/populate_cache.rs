@@ -0,0 +1,240 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+struct CacheState<K> {
+    entries: HashMap<K, (Arc<[u8]>, u64)>,
+    // last use tick to key, the first entry is the least recently used
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+    size: usize,
+}
+
+/// A bounded, thread safe LRU cache of byte blocks for populate functions to share
+///
+/// Objects whose populate functions read from the same expensive upstream can hold a clone of
+/// one `Arc<PopulateCache>` so a block decoded for one object is reused by the others.
+pub struct PopulateCache<K> {
+    capacity_bytes: usize,
+    state: Mutex<CacheState<K>>,
+}
+
+impl<K> PopulateCache<K>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(capacity_bytes: usize) -> Self {
+        PopulateCache {
+            capacity_bytes,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+                size: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState<K>> {
+        // the state is consistent between statements, so a panic elsewhere does not corrupt it
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &K) -> Option<Arc<[u8]>> {
+        let mut state = self.lock();
+        let state = &mut *state;
+        state.tick += 1;
+        let tick = state.tick;
+
+        let (value, last_used) = state.entries.get_mut(key)?;
+        state.recency.remove(last_used);
+        state.recency.insert(tick, key.clone());
+        *last_used = tick;
+        Some(value.clone())
+    }
+
+    /// Caches `value`, evicting least recently used blocks until it fits
+    ///
+    /// Blocks larger than the whole cache are returned without being cached.
+    pub fn insert(&self, key: K, value: impl Into<Arc<[u8]>>) -> Arc<[u8]> {
+        let value = value.into();
+        if value.len() > self.capacity_bytes {
+            return value;
+        }
+
+        let mut state = self.lock();
+        let state = &mut *state;
+        if let Some((old, last_used)) = state.entries.remove(&key) {
+            state.recency.remove(&last_used);
+            state.size -= old.len();
+        }
+
+        while state.size + value.len() > self.capacity_bytes {
+            let (_, lru) = state
+                .recency
+                .pop_first()
+                .expect("cache size accounting is out of sync with its entries");
+            let (evicted, _) = state.entries.remove(&lru).unwrap();
+            state.size -= evicted.len();
+        }
+
+        state.tick += 1;
+        state.size += value.len();
+        state.recency.insert(state.tick, key.clone());
+        state.entries.insert(key, (value.clone(), state.tick));
+        value
+    }
+
+    /// Returns the cached block or computes and caches it
+    ///
+    /// `load` runs without holding the cache lock, so two threads missing on the same key at the
+    /// same time may both compute it.
+    pub fn get_or_insert_with<F>(&self, key: K, load: F) -> Arc<[u8]>
+    where
+        F: FnOnce() -> Vec<u8>,
+    {
+        match self.get(&key) {
+            Some(value) => value,
+            None => self.insert(key, load()),
+        }
+    }
+
+    /// Like `get_or_insert_with` for loads that can fail, failures are not cached
+    pub fn try_get_or_insert_with<F, E>(&self, key: K, load: F) -> Result<Arc<[u8]>, E>
+    where
+        F: FnOnce() -> Result<Vec<u8>, E>,
+    {
+        match self.get(&key) {
+            Some(value) => Ok(value),
+            None => Ok(self.insert(key, load()?)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total bytes currently cached
+    pub fn size_bytes(&self) -> usize {
+        self.lock().size
+    }
+
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+    use std::{
+        mem::size_of,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use ufo_core::{UfoInternalErr, UfoObjectParams};
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = PopulateCache::new(10);
+        cache.insert(1, vec![1; 4]);
+        cache.insert(2, vec![2; 4]);
+        assert!(cache.get(&1).is_some());
+
+        // 2 is now the least recently used and has to make room
+        cache.insert(3, vec![3; 4]);
+        assert_eq!(2, cache.len());
+        assert_eq!(8, cache.size_bytes());
+        assert!(cache.get(&2).is_none());
+        assert_eq!(&[1; 4][..], &*cache.get(&1).unwrap());
+        assert_eq!(&[3; 4][..], &*cache.get(&3).unwrap());
+    }
+
+    #[test]
+    fn oversized_blocks_are_not_cached() {
+        let cache = PopulateCache::new(10);
+        cache.insert("small", vec![0; 2]);
+        let big = cache.insert("big", vec![0; 11]);
+
+        assert_eq!(11, big.len());
+        assert!(cache.get(&"big").is_none());
+        assert!(cache.get(&"small").is_some());
+    }
+
+    #[test]
+    fn replacing_a_key_updates_size() {
+        let cache = PopulateCache::new(10);
+        cache.insert(1, vec![0; 6]);
+        cache.insert(1, vec![0; 3]);
+
+        assert_eq!(1, cache.len());
+        assert_eq!(3, cache.size_bytes());
+    }
+
+    #[test]
+    fn shared_between_objects() -> Result<(), UfoInternalErr> {
+        let core = test_core();
+
+        let block_ct = 1024;
+        let cache = Arc::new(PopulateCache::new(1024 * 1024 * 16));
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let params = || {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            UfoObjectParams {
+                header_size: 0,
+                stride: size_of::<u32>(),
+                min_load_ct: Some(block_ct),
+                read_only: true,
+                element_ct: block_ct * 64,
+                populate: Box::new(move |start, end, fill| {
+                    let mut idx = start;
+                    while idx < end {
+                        let block = idx / block_ct;
+                        let block_end = ((block + 1) * block_ct).min(end);
+                        let data = cache.get_or_insert_with(block, || {
+                            loads.fetch_add(1, Ordering::Relaxed);
+                            (block * block_ct..(block + 1) * block_ct)
+                                .flat_map(|i| (i as u32).to_ne_bytes())
+                                .collect()
+                        });
+
+                        let offset = (idx - block * block_ct) * size_of::<u32>();
+                        let len = (block_end - idx) * size_of::<u32>();
+                        unsafe {
+                            std::ptr::copy_nonoverlapping(
+                                data[offset..offset + len].as_ptr(),
+                                fill.add((idx - start) * size_of::<u32>()),
+                                len,
+                            )
+                        };
+                        idx = block_end;
+                    }
+                    Ok(())
+                }),
+                writeback_listener: None,
+            }
+        };
+
+        let a = core.new_ufo(params())?;
+        let b = core.new_ufo(params())?;
+        for o in &[&a, &b] {
+            let arr = unsafe {
+                std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), block_ct * 64)
+            };
+            for (i, x) in arr.iter().enumerate() {
+                assert_eq!(i as u32, *x);
+            }
+        }
+
+        assert_eq!(64, loads.load(Ordering::Relaxed));
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    io,
+    ops::Range,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crossbeam::channel::{self, Sender};
+use ufo_core::WrappedUfoObject;
+
+use crate::{residency, system_page_size};
+
+/// A range of one object to bring into memory ahead of access
+pub(crate) struct PrefetchJob {
+    pub(crate) slot: Arc<RwLock<Option<WrappedUfoObject>>>,
+    // held for reading while the job touches the body, see `UfoHandle::prefetches`
+    pub(crate) running: Arc<RwLock<()>>,
+    pub(crate) body: usize,
+    pub(crate) body_size: usize,
+    pub(crate) stride: usize,
+    pub(crate) range: Range<usize>,
+    // elements brought in
+    pub(crate) done: Sender<usize>,
+}
+
+/// Runs every prefetch of one core on a single background thread, in the order they were
+/// queued
+///
+/// Also knows the body of every live object on the core, so the budget for a prefetch is what
+/// the whole core has resident, not just the object being prefetched.
+pub(crate) struct Prefetcher {
+    jobs: Sender<PrefetchJob>,
+    // body address to body size
+    bodies: Arc<Mutex<HashMap<usize, usize>>>,
+}
+
+impl Prefetcher {
+    pub(crate) fn new(high_watermark: usize) -> io::Result<Arc<Self>> {
+        let (jobs, queue) = channel::unbounded::<PrefetchJob>();
+        let bodies = Arc::new(Mutex::new(HashMap::new()));
+        let live = bodies.clone();
+        std::thread::Builder::new()
+            .name("ufo-prefetch".to_string())
+            .spawn(move || {
+                for job in queue.iter() {
+                    job.run(&live, high_watermark);
+                }
+            })?;
+        Ok(Arc::new(Prefetcher { jobs, bodies }))
+    }
+
+    /// Counts the object towards the core's residency until `unregister`
+    pub(crate) fn register(&self, ufo: &WrappedUfoObject, body_size: usize) {
+        if let Ok(ufo) = ufo.read() {
+            let mut bodies = self.bodies.lock().unwrap_or_else(|e| e.into_inner());
+            bodies.insert(ufo.body_ptr() as usize, body_size);
+        }
+    }
+
+    pub(crate) fn unregister(&self, ufo: &WrappedUfoObject) {
+        if let Ok(ufo) = ufo.read() {
+            let mut bodies = self.bodies.lock().unwrap_or_else(|e| e.into_inner());
+            bodies.remove(&(ufo.body_ptr() as usize));
+        }
+    }
+
+    pub(crate) fn queue(&self, job: PrefetchJob) {
+        // the worker only stops once every sender is gone, so this cannot fail in practice and
+        // a dropped job would still report through its waiter
+        let _ = self.jobs.send(job);
+    }
+}
+
+// Bytes in memory across every registered body
+fn resident_bytes(bodies: &Mutex<HashMap<usize, usize>>) -> usize {
+    let bodies = bodies.lock().unwrap_or_else(|e| e.into_inner());
+    let pages: usize = bodies
+        .iter()
+        .map(|(&body, &size)| {
+            // a body freed since the check is unmapped, mincore refuses it and it counts nothing
+            residency(body as *const u8, size)
+                .map(|pages| pages.into_iter().filter(|r| *r).count())
+                .unwrap_or(0)
+        })
+        .sum();
+    pages * system_page_size()
+}
+
+impl PrefetchJob {
+    fn run(self, bodies: &Mutex<HashMap<usize, usize>>, high_watermark: usize) {
+        // a free waits for this before it looks at the slot, so an object still there now
+        // outlives the prefetch
+        let running = self.running.read().unwrap_or_else(|e| e.into_inner());
+        if self
+            .slot
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none()
+        {
+            let _ = self.done.send(0);
+            return;
+        }
+
+        let body = self.body as *const u8;
+        let (start, end, stride) = (self.range.start, self.range.end, self.stride);
+        let page_size = system_page_size();
+        let offset = body as usize % page_size;
+        let resident = residency(body, self.body_size).unwrap_or_default();
+        let mut budget = high_watermark.saturating_sub(resident_bytes(bodies));
+
+        let first = (offset + start * stride) / page_size;
+        let last = (offset + end * stride).div_ceil(page_size);
+        let mut queued = 0;
+        for page in first..last {
+            if resident.get(page).copied().unwrap_or(false) {
+                continue;
+            }
+            if budget < page_size {
+                break;
+            }
+            budget -= page_size;
+
+            // elements starting on this page, clipped to the requested range
+            let page_start = (page * page_size).saturating_sub(offset);
+            let page_end = ((page + 1) * page_size - offset).min(self.body_size);
+            let from = page_start.div_ceil(stride).max(start);
+            let to = page_end.div_ceil(stride).min(end);
+            queued += to.saturating_sub(from);
+
+            unsafe { std::ptr::read_volatile(body.add(page_start)) };
+        }
+        drop(running);
+        let _ = self.done.send(queued);
+    }
+}
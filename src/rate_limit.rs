@@ -0,0 +1,79 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Spaces populate calls out so their combined output stays under a byte rate
+pub(crate) struct PopulatePacer {
+    // 0 means unlimited
+    bytes_per_sec: AtomicU64,
+    // earliest time the next populate may start
+    next: Mutex<Instant>,
+}
+
+impl PopulatePacer {
+    pub(crate) fn new() -> Self {
+        PopulatePacer {
+            bytes_per_sec: AtomicU64::new(0),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub(crate) fn set_limit(&self, bytes_per_sec: Option<u64>) {
+        self.bytes_per_sec
+            .store(bytes_per_sec.unwrap_or(0), Ordering::Release);
+    }
+
+    /// Blocks until `bytes` more can be produced without exceeding the limit
+    pub(crate) fn pace(&self, bytes: usize) {
+        let rate = self.bytes_per_sec.load(Ordering::Acquire);
+        if rate == 0 {
+            return;
+        }
+
+        let cost = Duration::from_secs_f64(bytes as f64 / rate as f64);
+        let start = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            // idle time does not build up credit for a later burst
+            let start = (*next).max(Instant::now());
+            *next = start + cost;
+            start
+        };
+
+        let now = Instant::now();
+        if start > now {
+            std::thread::sleep(start - now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_does_not_wait() {
+        let pacer = PopulatePacer::new();
+        let started = Instant::now();
+        for _ in 0..100 {
+            pacer.pace(1024 * 1024 * 1024);
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn spaces_out_populates() {
+        let pacer = PopulatePacer::new();
+        pacer.set_limit(Some(10 * 1024 * 1024));
+
+        let started = Instant::now();
+        for _ in 0..4 {
+            pacer.pace(1024 * 1024);
+        }
+        // the first chunk goes out right away, the other three wait 100ms each
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+}
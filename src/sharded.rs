@@ -0,0 +1,154 @@
+use bytemuck::Pod;
+use ufo_core::UfoInternalErr;
+
+use crate::{LazyVec, UfoCore, UfoErr, UfoStats};
+
+/// Consecutive keys that land in the same shard
+pub const SHARD_BLOCK: usize = 4096;
+
+/// A keyspace too large for one object, spread across several objects
+///
+/// Keys are dealt to shards in blocks of `SHARD_BLOCK`, round robin, so every shard stays
+/// densely indexed and a populate call for one shard covers consecutive keys.
+///
+/// Routing is deliberately not by key hash. Each shard is an array, so every key needs a dense
+/// position inside its shard; hashing keys to shards would either leave holes that still take
+/// address space or need a key to position directory as large as the keyspace. Dealing fixed
+/// blocks spreads keys evenly with neither. For the same reason keys are `usize` indexes, other
+/// key types have to be mapped to dense indexes first.
+pub struct ShardedUfo<T> {
+    shards: Vec<LazyVec<T>>,
+    len: usize,
+}
+
+impl<T> ShardedUfo<T>
+where
+    T: Pod,
+{
+    /// Allocates `shard_ct` objects covering keys `0..len`, each generating its own keys
+    pub fn new<F>(
+        core: &UfoCore,
+        shard_ct: usize,
+        len: usize,
+        generator: F,
+    ) -> Result<Self, UfoInternalErr>
+    where
+        F: Fn(usize) -> T + Send + Sync + Clone + 'static,
+    {
+        assert!(shard_ct > 0, "ShardedUfo needs at least one shard");
+
+        let shards = (0..shard_ct)
+            .map(|shard| {
+                let generator = generator.clone();
+                let shard_len = Self::shard_len(shard_ct, len, shard);
+                LazyVec::with_min_load_ct(core, shard_len, Some(SHARD_BLOCK), move |local| {
+                    generator(Self::global_key(shard_ct, shard, local))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ShardedUfo { shards, len })
+    }
+
+    fn shard_len(shard_ct: usize, len: usize, shard: usize) -> usize {
+        let blocks = len.div_ceil(SHARD_BLOCK);
+        let owned = blocks / shard_ct + usize::from(shard < blocks % shard_ct);
+        let mut shard_len = owned * SHARD_BLOCK;
+        // only the very last block can be partial
+        if blocks > 0 && (blocks - 1) % shard_ct == shard {
+            shard_len -= blocks * SHARD_BLOCK - len;
+        }
+        shard_len
+    }
+
+    fn global_key(shard_ct: usize, shard: usize, local: usize) -> usize {
+        let block = (local / SHARD_BLOCK) * shard_ct + shard;
+        block * SHARD_BLOCK + local % SHARD_BLOCK
+    }
+
+    fn route(&self, key: usize) -> (usize, usize) {
+        assert!(key < self.len, "key {} out of bounds for {} keys", key, self.len);
+        let block = key / SHARD_BLOCK;
+        let shard = block % self.shards.len();
+        (shard, (block / self.shards.len()) * SHARD_BLOCK + key % SHARD_BLOCK)
+    }
+
+    pub fn get(&self, key: usize) -> T {
+        let (shard, local) = self.route(key);
+        self.shards[shard][local]
+    }
+
+    pub fn set(&mut self, key: usize, value: T) {
+        let (shard, local) = self.route(key);
+        self.shards[shard][local] = value;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn shard_ct(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Elements currently in memory, summed over every shard
+    pub fn resident_elements(&self) -> Result<usize, UfoErr> {
+        self.shards
+            .iter()
+            .map(|shard| Ok(shard.handle().iter_resident::<T>()?.count()))
+            .sum()
+    }
+
+    /// Activity summed over every shard
+    ///
+    /// Populate calls beyond the first access to a range are reloads after the core evicted it,
+    /// so this also shows eviction pressure across the whole keyspace.
+    pub fn stats(&self) -> UfoStats {
+        let mut stats = UfoStats::default();
+        for shard in &self.shards {
+            stats.accumulate(&shard.handle().stats());
+        }
+        stats
+    }
+
+    /// Discards all writes in every shard
+    pub fn reset(&mut self) -> Result<(), UfoInternalErr> {
+        self.shards.iter_mut().try_for_each(|shard| shard.reset())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+
+    #[test]
+    fn routes_every_key() -> Result<(), UfoErr> {
+        let core = test_core();
+        // 3 shards over a keyspace that ends in a partial block
+        let len = SHARD_BLOCK * 10 + 17;
+        let mut keys = ShardedUfo::new(&core, 3, len, |k| k as u64 * 2)?;
+
+        let total: usize = (0..3).map(|s| ShardedUfo::<u64>::shard_len(3, len, s)).sum();
+        assert_eq!(len, total);
+        for k in 0..len {
+            assert_eq!(k as u64 * 2, keys.get(k));
+        }
+
+        keys.set(SHARD_BLOCK * 4 + 1, 7);
+        assert_eq!(7, keys.get(SHARD_BLOCK * 4 + 1));
+        assert_eq!((SHARD_BLOCK * 4 + 2) as u64 * 2, keys.get(SHARD_BLOCK * 4 + 2));
+        assert!(keys.resident_elements()? > 0);
+        let stats = keys.stats();
+        assert!(stats.chunks_populated >= 3);
+        assert!(stats.bytes_populated >= (len * 8) as u64);
+
+        keys.reset()?;
+        assert_eq!((SHARD_BLOCK * 4 + 1) as u64 * 2, keys.get(SHARD_BLOCK * 4 + 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::{ops::Deref, sync::Arc};
+
+use crate::UfoHandle;
+
+/// A cheaply clonable handle to a read only object, for sharing it between threads
+///
+/// Every clone refers to the same object, which is freed when the last clone is dropped or
+/// when any clone calls `free`. Threads reading the body should do so through `read_guard` or
+/// `as_slice`, which make a concurrent `free` fail with `UfoErr::InUse` and a concurrent `reset`
+/// wait until they are dropped.
+#[derive(Clone)]
+pub struct SharedUfo(Arc<UfoHandle>);
+
+impl UfoHandle {
+    /// Turns the handle into a `SharedUfo`, writable objects are handed back unchanged
+    ///
+    /// Sharing is limited to read only objects, the core protects their bodies so no clone can
+    /// race another with a write.
+    #[allow(clippy::result_large_err)]
+    pub fn into_shared(self) -> Result<SharedUfo, UfoHandle> {
+        if !self.info().read_only {
+            return Err(self);
+        }
+        Ok(SharedUfo(Arc::new(self)))
+    }
+}
+
+impl Deref for SharedUfo {
+    type Target = UfoHandle;
+
+    fn deref(&self) -> &UfoHandle {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::test_core, UfoErr};
+    use std::mem::size_of;
+    use ufo_core::{UfoInternalErr, UfoObjectParams};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    fn params(read_only: bool) -> UfoObjectParams {
+        UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: Some(1024),
+            read_only,
+            element_ct: 1000 * 1000,
+            populate: Box::new(|start, end, fill| {
+                for idx in start..end {
+                    unsafe { fill.cast::<u32>().add(idx - start).write(idx as u32) };
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        }
+    }
+
+    #[test]
+    fn concurrent_readers() -> Result<(), UfoErr> {
+        assert_send_sync::<SharedUfo>();
+        let core = test_core();
+        assert!(core.new_ufo(params(false))?.into_shared().is_err());
+
+        let shared = core.new_ufo(params(true))?.into_shared().ok().unwrap();
+        let readers: Vec<_> = (0..8)
+            .map(|reader| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let arr = shared.as_slice::<u32>().unwrap();
+                    // interleaved so every thread faults pages the others also want
+                    (reader..arr.len())
+                        .step_by(8 * 1024)
+                        .all(|i| arr[i] == i as u32)
+                })
+            })
+            .collect();
+        for reader in readers {
+            assert!(reader.join().unwrap());
+        }
+
+        let guard = shared.read_guard()?;
+        assert_eq!(4 * 1000 * 1000, guard.len());
+        drop(guard);
+
+        let other = shared.clone();
+        shared.free()?;
+        assert!(matches!(
+            other.as_slice::<u32>(),
+            Err(UfoErr::Core(UfoInternalErr::UfoNotFound))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    mem::size_of,
+    ops::{Deref, DerefMut, Range},
+    sync::RwLockReadGuard,
+};
+
+use bytemuck::Pod;
+use ufo_core::{UfoInternalErr, WrappedUfoObject};
+
+use crate::{UfoErr, UfoHandle};
+
+/// A `[T]` view of an object's body returned by `UfoHandle::as_slice` and `as_bytes`
+///
+/// The view holds the handle's object for reading, so the slice can never dangle: `free` fails
+/// with `UfoErr::InUse` and `reset` blocks until it is dropped. Resetting from the thread
+/// holding the view deadlocks.
+pub struct UfoSlice<'a, T> {
+    _slot: RwLockReadGuard<'a, Option<WrappedUfoObject>>,
+    handle: &'a UfoHandle,
+    slice: &'a [T],
+}
+
+/// Iterator over fixed size windows of a `UfoSlice`, see `UfoSlice::streaming_chunks`
+pub struct StreamingChunks<'s, T> {
+    handle: &'s UfoHandle,
+    slice: &'s [T],
+    chunk_elems: usize,
+    next: usize,
+}
+
+/// Mutable counterpart of `UfoSlice` returned by `UfoHandle::as_mut_slice`
+pub struct UfoSliceMut<'a, T> {
+    _slot: RwLockReadGuard<'a, Option<WrappedUfoObject>>,
+    slice: &'a mut [T],
+}
+
+impl UfoHandle {
+    /// Views the body as `[T]`, `T` has to be the size of the object's stride
+    ///
+    /// `T` is `Pod` because the body holds whatever bytes populate produced, or zeros after a
+    /// failed populate, and every one of them has to be a valid `T`.
+    pub fn as_slice<T: Pod>(&self) -> Result<UfoSlice<'_, T>, UfoErr> {
+        let (slot, body) = self.typed_body::<T>()?;
+        Ok(UfoSlice {
+            _slot: slot,
+            handle: self,
+            slice: unsafe { std::slice::from_raw_parts(body, self.info.element_ct) },
+        })
+    }
+
+    /// The body as raw bytes, `stride * element_ct` long, holding the object like `as_slice`
+    pub fn as_bytes(&self) -> Result<UfoSlice<'_, u8>, UfoErr> {
+        let (slot, body) = self.locked_body::<u8>()?;
+        Ok(UfoSlice {
+            _slot: slot,
+            handle: self,
+            slice: unsafe { std::slice::from_raw_parts(body, self.info.body_size()) },
+        })
+    }
+
+    /// Like `as_bytes` for writing, fails on read only and sealed objects
+    pub fn as_bytes_mut(&mut self) -> Result<UfoSliceMut<'_, u8>, UfoErr> {
+        self.writable()?;
+        let (slot, body) = self.locked_body::<u8>()?;
+        Ok(UfoSliceMut {
+            _slot: slot,
+            slice: unsafe { std::slice::from_raw_parts_mut(body, self.info.body_size()) },
+        })
+    }
+
+    /// Keeps the object from being freed or reset while it is held, same as `as_bytes`
+    pub fn read_guard(&self) -> Result<UfoSlice<'_, u8>, UfoErr> {
+        self.as_bytes()
+    }
+
+    /// Like `as_slice` for writing, fails on read only and sealed objects
+    pub fn as_mut_slice<T: Pod>(&mut self) -> Result<UfoSliceMut<'_, T>, UfoErr> {
+        self.writable()?;
+        let (slot, body) = self.typed_body::<T>()?;
+        Ok(UfoSliceMut {
+            _slot: slot,
+            slice: unsafe { std::slice::from_raw_parts_mut(body, self.info.element_ct) },
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn typed_body<T: Pod>(
+        &self,
+    ) -> Result<(RwLockReadGuard<'_, Option<WrappedUfoObject>>, *mut T), UfoErr> {
+        self.check_element::<T>()?;
+        self.locked_body()
+    }
+
+    // Elements of type `T` have to be exactly one stride wide
+    pub(crate) fn check_element<T>(&self) -> Result<(), UfoErr> {
+        if self.info.stride != size_of::<T>() {
+            return Err(UfoErr::ElementSize {
+                stride: self.info.stride,
+                element_size: size_of::<T>(),
+            });
+        }
+        Ok(())
+    }
+
+    // Holds the object for reading, every view of the body has to keep the guard next to it
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn locked_body<T>(
+        &self,
+    ) -> Result<(RwLockReadGuard<'_, Option<WrappedUfoObject>>, *mut T), UfoErr> {
+        self.alive()?;
+        let slot = self.slot()?;
+        let body = slot
+            .as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)?
+            .read()?
+            .body_ptr();
+        Ok((slot, body.cast()))
+    }
+
+    // Like `locked_body` for the header
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn locked_header(
+        &self,
+    ) -> Result<(RwLockReadGuard<'_, Option<WrappedUfoObject>>, *mut u8), UfoErr> {
+        self.alive()?;
+        let slot = self.slot()?;
+        let header = slot
+            .as_ref()
+            .ok_or(UfoInternalErr::UfoNotFound)?
+            .read()?
+            .header_ptr();
+        Ok((slot, header.cast()))
+    }
+}
+
+impl<T> UfoSlice<'_, T> {
+    // Narrows the view to `range` of its elements, still holding the object
+    pub(crate) fn narrow(self, range: Range<usize>) -> Self {
+        let slice = &self.slice[range];
+        UfoSlice { slice, ..self }
+    }
+
+    /// Iterates over windows of `chunk_elems` elements, the last one may be shorter
+    ///
+    /// Yielding a window starts a background prefetch of the next one, so populating it
+    /// overlaps with work on the current window. The prefetch is queued on the core's one
+    /// prefetch worker, so no thread is started per window and windows can be as small as
+    /// the work on them allows. Windows already passed are left to the core's normal
+    /// eviction, releasing them early would need the core's help.
+    pub fn streaming_chunks(&self, chunk_elems: usize) -> StreamingChunks<'_, T> {
+        assert!(chunk_elems > 0, "windows must not be empty");
+        StreamingChunks {
+            handle: self.handle,
+            slice: self.slice,
+            chunk_elems,
+            next: 0,
+        }
+    }
+}
+
+impl<'s, T> Iterator for StreamingChunks<'s, T> {
+    type Item = &'s [T];
+
+    fn next(&mut self) -> Option<&'s [T]> {
+        let len = self.slice.len();
+        if self.next >= len {
+            return None;
+        }
+        let start = self.next;
+        let end = (start + self.chunk_elems).min(len);
+        self.next = end;
+
+        let ahead = (end + self.chunk_elems).min(len);
+        if end < ahead {
+            // the waiter is not needed, the prefetch only has to run ahead of the reader
+            let _ = self
+                .handle
+                .prefetch_body(self.slice.as_ptr().cast(), end, ahead);
+        }
+        Some(&self.slice[start..end])
+    }
+}
+
+impl<T> Deref for UfoSlice<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<T> Deref for UfoSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.slice
+    }
+}
+
+impl<T> DerefMut for UfoSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.slice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_core;
+    use ufo_core::UfoObjectParams;
+
+    #[test]
+    fn typed_views() -> Result<(), UfoErr> {
+        let core = test_core();
+        let mut o = core.new_ufo(UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: None,
+            read_only: false,
+            element_ct: 1000,
+            populate: Box::new(|start, end, fill| {
+                for idx in start..end {
+                    unsafe { fill.cast::<u32>().add(idx - start).write(idx as u32) };
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        })?;
+
+        assert!(matches!(
+            o.as_slice::<u64>(),
+            Err(UfoErr::ElementSize {
+                stride: 4,
+                element_size: 8
+            })
+        ));
+
+        o.as_mut_slice::<u32>()?[10] = 0;
+        let arr = o.as_slice::<u32>()?;
+        assert_eq!(1000, arr.len());
+        assert_eq!(0, arr[10]);
+        assert_eq!(999, arr[999]);
+        drop(arr);
+
+        let windows = o.as_slice::<u32>()?;
+        let lens: Vec<_> = windows.streaming_chunks(300).map(<[u32]>::len).collect();
+        assert_eq!(vec![300, 300, 300, 100], lens);
+        let joined: Vec<u32> = windows.streaming_chunks(300).flatten().copied().collect();
+        assert_eq!(windows.to_vec(), joined);
+        drop(windows);
+
+        o.free()?;
+        assert!(matches!(
+            o.as_slice::<u32>(),
+            Err(UfoErr::Core(UfoInternalErr::UfoNotFound))
+        ));
+        Ok(())
+    }
+}
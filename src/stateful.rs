@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+use ufo_core::{UfoPopulateError, UfoPopulateFn};
+
+/// A populate function that owns mutable state, for `UfoObjectParams::populate`
+///
+/// The core may populate from several threads at once, these calls are serialized on one lock.
+/// Use `populate_pooled` when populating in parallel matters.
+pub fn populate_serialized<F>(populate: F) -> Box<UfoPopulateFn>
+where
+    F: FnMut(usize, usize, *mut u8) -> Result<(), UfoPopulateError> + Send + 'static,
+{
+    let populate = Mutex::new(populate);
+    Box::new(move |start, end, fill| {
+        let mut populate = populate.lock().map_err(|_| UfoPopulateError)?;
+        populate(start, end, fill)
+    })
+}
+
+/// A populate function with one state per concurrent call, created by `new_state` as needed
+///
+/// Each call takes a state out of a pool and returns it afterwards, so no two calls share a
+/// state and at most as many are created as the core runs populate calls at once. Calls are
+/// not tied to a thread or to a range, anything reproducible has to be derived from the
+/// element indices rather than from the state's history.
+pub fn populate_pooled<S, N, F>(new_state: N, populate: F) -> Box<UfoPopulateFn>
+where
+    S: Send + 'static,
+    N: Fn() -> S + Send + Sync + 'static,
+    F: Fn(&mut S, usize, usize, *mut u8) -> Result<(), UfoPopulateError> + Send + Sync + 'static,
+{
+    let pool = Mutex::new(Vec::new());
+    Box::new(move |start, end, fill| {
+        let taken = pool.lock().map_err(|_| UfoPopulateError)?.pop();
+        let mut state = taken.unwrap_or_else(&new_state);
+        let result = populate(&mut state, start, end, fill);
+        pool.lock().map_err(|_| UfoPopulateError)?.push(state);
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::test_core, UfoErr};
+    use std::{
+        mem::size_of,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use ufo_core::UfoObjectParams;
+
+    fn params(element_ct: usize, populate: Box<UfoPopulateFn>) -> UfoObjectParams {
+        UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: Some(1024),
+            read_only: false,
+            element_ct,
+            populate,
+            writeback_listener: None,
+        }
+    }
+
+    #[test]
+    fn stateful_populate() -> Result<(), UfoErr> {
+        let core = test_core();
+
+        let mut calls = 0u32;
+        let serialized = core.new_ufo(params(
+            10 * 1024,
+            populate_serialized(move |start, end, fill| {
+                calls += 1;
+                for idx in start..end {
+                    unsafe { fill.cast::<u32>().add(idx - start).write(calls) };
+                }
+                Ok(())
+            }),
+        ))?;
+        // each call numbered its range, whatever order and size the core chose
+        let mut seen = serialized.as_slice::<u32>()?.to_vec();
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!((1..=seen.len() as u32).collect::<Vec<_>>(), seen);
+
+        let states = Arc::new(AtomicUsize::new(0));
+        let pooled = core.new_ufo(params(
+            10 * 1024,
+            populate_pooled(
+                {
+                    let states = states.clone();
+                    move || states.fetch_add(1, Ordering::Relaxed)
+                },
+                |_, start, end, fill| {
+                    for idx in start..end {
+                        unsafe { fill.cast::<u32>().add(idx - start).write(idx as u32) };
+                    }
+                    Ok(())
+                },
+            ),
+        ))?;
+        for (i, x) in pooled.as_slice::<u32>()?.iter().enumerate() {
+            assert_eq!(i as u32, *x);
+        }
+        assert!(states.load(Ordering::Relaxed) >= 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Buckets in `UfoStats::populate_time_histogram`
+pub const POPULATE_TIME_BUCKETS: usize = 24;
+
+/// Activity of one object since it was allocated or since its stats were last reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UfoStats {
+    /// Calls made to the populate function, each serving at least one fault
+    pub chunks_populated: u64,
+    pub bytes_populated: u64,
+    /// Time spent inside the populate function
+    pub populate_time: Duration,
+    /// Populate calls by duration, bucket 0 counts calls under 1µs and bucket `i` calls of
+    /// `2^(i-1)` up to `2^i` µs, the last bucket also takes everything slower
+    pub populate_time_histogram: [u64; POPULATE_TIME_BUCKETS],
+    /// Ranges handed to the writeback listener, only counted when the object has one
+    pub writebacks: u64,
+    pub bytes_written_back: u64,
+}
+
+impl UfoStats {
+    // Adds another object's activity to this one
+    pub(crate) fn accumulate(&mut self, other: &UfoStats) {
+        self.chunks_populated += other.chunks_populated;
+        self.bytes_populated += other.bytes_populated;
+        self.populate_time += other.populate_time;
+        for (bucket, other) in self
+            .populate_time_histogram
+            .iter_mut()
+            .zip(&other.populate_time_histogram)
+        {
+            *bucket += other;
+        }
+        self.writebacks += other.writebacks;
+        self.bytes_written_back += other.bytes_written_back;
+    }
+}
+
+/// Counters maintained by the populate wrapper installed on every object
+#[derive(Default)]
+pub(crate) struct PopulateCounters {
+    calls: AtomicU64,
+    bytes: AtomicU64,
+    nanos: AtomicU64,
+    histogram: [AtomicU64; POPULATE_TIME_BUCKETS],
+}
+
+impl PopulateCounters {
+    pub(crate) fn record(&self, bytes: u64, elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        let micros = elapsed.as_micros() as u64;
+        let bucket = (64 - micros.leading_zeros() as usize).min(POPULATE_TIME_BUCKETS - 1);
+        self.histogram[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn throughput_bps(&self) -> f64 {
+        let nanos = self.nanos.load(Ordering::Relaxed);
+        if nanos == 0 {
+            return 0.0;
+        }
+        self.bytes.load(Ordering::Relaxed) as f64 * 1e9 / nanos as f64
+    }
+
+    pub(crate) fn fill(&self, stats: &mut UfoStats) {
+        stats.chunks_populated = self.calls.load(Ordering::Relaxed);
+        stats.bytes_populated = self.bytes.load(Ordering::Relaxed);
+        stats.populate_time = Duration::from_nanos(self.nanos.load(Ordering::Relaxed));
+        stats.populate_time_histogram =
+            std::array::from_fn(|i| self.histogram[i].load(Ordering::Relaxed));
+    }
+
+    pub(crate) fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.nanos.store(0, Ordering::Relaxed);
+        for bucket in &self.histogram {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counters maintained by the writeback listener wrapper
+#[derive(Default)]
+pub(crate) struct WritebackCounters {
+    writebacks: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl WritebackCounters {
+    pub(crate) fn record(&self, bytes: u64) {
+        self.writebacks.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fill(&self, stats: &mut UfoStats) {
+        stats.writebacks = self.writebacks.load(Ordering::Relaxed);
+        stats.bytes_written_back = self.bytes.load(Ordering::Relaxed);
+    }
+
+    pub(crate) fn reset(&self) {
+        self.writebacks.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Names of the metrics published through the `metrics` facade, these are stable
+#[cfg(feature = "metrics")]
+pub mod metric_names {
+    /// Counter of calls to populate functions, one call can resolve several faults
+    pub const POPULATE_CALLS: &str = "ufo.populate_calls";
+    /// Counter of bytes produced by populate functions
+    pub const BYTES_POPULATED: &str = "ufo.bytes_populated";
+    /// Histogram of the time each populate call took, in seconds
+    pub const POPULATE_SECONDS: &str = "ufo.populate_seconds";
+    /// Counter of ranges handed to writeback listeners
+    pub const WRITEBACKS: &str = "ufo.writebacks";
+    /// Counter of bytes handed to writeback listeners
+    pub const BYTES_WRITTEN_BACK: &str = "ufo.bytes_written_back";
+    /// Gauge of objects alive across every core
+    pub const UFO_COUNT: &str = "ufo.ufo_count";
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn publish_populate(bytes: u64, elapsed: Duration) {
+    metrics::counter!(metric_names::POPULATE_CALLS).increment(1);
+    metrics::counter!(metric_names::BYTES_POPULATED).increment(bytes);
+    metrics::histogram!(metric_names::POPULATE_SECONDS).record(elapsed.as_secs_f64());
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn publish_populate(_bytes: u64, _elapsed: Duration) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn publish_writeback(bytes: u64) {
+    metrics::counter!(metric_names::WRITEBACKS).increment(1);
+    metrics::counter!(metric_names::BYTES_WRITTEN_BACK).increment(bytes);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn publish_writeback(_bytes: u64) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn publish_ufo_count_change(delta: f64) {
+    metrics::gauge!(metric_names::UFO_COUNT).increment(delta);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn publish_ufo_count_change(_delta: f64) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn populate_time_buckets() {
+        let counters = PopulateCounters::default();
+        counters.record(8, Duration::from_nanos(500));
+        counters.record(8, Duration::from_micros(3));
+        counters.record(8, Duration::from_secs(3600));
+
+        let mut stats = UfoStats::default();
+        counters.fill(&mut stats);
+        assert_eq!(1, stats.populate_time_histogram[0]);
+        assert_eq!(1, stats.populate_time_histogram[2]);
+        assert_eq!(1, stats.populate_time_histogram[POPULATE_TIME_BUCKETS - 1]);
+        assert_eq!(3, stats.populate_time_histogram.iter().sum::<u64>());
+    }
+}
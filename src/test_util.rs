@@ -0,0 +1,38 @@
+//! Helpers shared by the test modules
+
+use std::mem::size_of;
+
+use ufo_core::{UfoCoreConfig, UfoObjectParams};
+
+use crate::UfoCore;
+
+pub(crate) fn test_config() -> UfoCoreConfig {
+    UfoCoreConfig {
+        writeback_temp_path: "/tmp".to_string(),
+        high_watermark: 1024 * 1024 * 1024,
+        low_watermark: 512 * 1024 * 1024,
+    }
+}
+
+pub(crate) fn test_core() -> UfoCore {
+    UfoCore::new_ufo_core(test_config()).expect("error getting core")
+}
+
+// u32 elements holding their own index
+pub(crate) fn sequence_params(element_ct: usize) -> UfoObjectParams {
+    UfoObjectParams {
+        header_size: 0,
+        stride: size_of::<u32>(),
+        min_load_ct: None,
+        read_only: false,
+        element_ct,
+        populate: Box::new(|start, end, fill| {
+            let slice = unsafe { std::slice::from_raw_parts_mut::<u32>(fill.cast(), end - start) };
+            for idx in start..end {
+                slice[idx - start] = idx as u32;
+            }
+            Ok(())
+        }),
+        writeback_listener: None,
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ufo_core::{UfoObjectParams, UfoPopulateError};
+
+struct Failure {
+    elems: Range<usize>,
+    remaining: AtomicUsize,
+}
+
+/// Populate failures and delays to inject into an object, for testing code that uses it
+///
+/// Faults are matched against the elements each populate call covers, so whether one fires
+/// depends only on which chunks get loaded, never on timing. Writeback errors, forced evictions
+/// and stepping the core's threads by hand are out of reach, those happen inside the core.
+#[derive(Default)]
+pub struct FaultPlan {
+    failures: Vec<Failure>,
+    delays: Vec<(Range<usize>, Duration)>,
+}
+
+impl FaultPlan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the first `times` populate calls covering any of `elems`
+    pub fn fail(mut self, elems: Range<usize>, times: usize) -> Self {
+        self.failures.push(Failure {
+            elems,
+            remaining: AtomicUsize::new(times),
+        });
+        self
+    }
+
+    /// Holds up every populate call covering any of `elems` by `delay` before it runs
+    pub fn delay(mut self, elems: Range<usize>, delay: Duration) -> Self {
+        self.delays.push((elems, delay));
+        self
+    }
+
+    /// Wraps the populate function of `params` so the plan applies to the object
+    pub fn apply(self, params: UfoObjectParams) -> UfoObjectParams {
+        let populate = params.populate;
+        UfoObjectParams {
+            populate: Box::new(move |start, end, fill| {
+                let covers = |elems: &Range<usize>| elems.start < end && start < elems.end;
+                let delay = self
+                    .delays
+                    .iter()
+                    .filter(|(elems, _)| covers(elems))
+                    .map(|(_, delay)| *delay)
+                    .sum::<Duration>();
+                if !delay.is_zero() {
+                    std::thread::sleep(delay);
+                }
+                for failure in self.failures.iter().filter(|f| covers(&f.elems)) {
+                    let fired = failure
+                        .remaining
+                        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+                        .is_ok();
+                    if fired {
+                        return Err(UfoPopulateError);
+                    }
+                }
+                populate(start, end, fill)
+            }),
+            ..params
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::test_core, UfoErr};
+    use std::{mem::size_of, time::Instant};
+
+    #[test]
+    fn injected_faults() -> Result<(), UfoErr> {
+        let core = test_core();
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: Some(1024),
+            read_only: false,
+            element_ct: 1000 * 1000,
+            populate: Box::new(|start, end, fill| {
+                for idx in start..end {
+                    unsafe { fill.cast::<u32>().add(idx - start).write(idx as u32) };
+                }
+                Ok(())
+            }),
+            writeback_listener: None,
+        };
+        let plan = FaultPlan::new()
+            .fail(5..6, 1)
+            .delay(500 * 1000..500 * 1001, Duration::from_millis(50));
+        let started = Instant::now();
+        let o = core.new_ufo(plan.apply(params))?;
+
+        let arr = o.as_slice::<u32>()?;
+        assert_eq!(0, arr[5]);
+        assert_eq!(500 * 1000, arr[500 * 1000]);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        drop(arr);
+        assert!(o.is_poisoned());
+
+        // the failure only fires once, a retry after reset goes through
+        o.reset()?;
+        assert!(!o.is_poisoned());
+        assert_eq!(5, o.as_slice::<u32>()?[5]);
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, marker::PhantomData, mem::size_of, sync::RwLockReadGuard};
+
+use bytemuck::Pod;
+use ufo_core::WrappedUfoObject;
+
+use crate::{system_page_size, UfoErr, UfoHandle};
+
+/// Typed view of an object's body handed to `UfoHandle::transaction`
+///
+/// The first write to each page saves that page's original contents, if the transaction fails
+/// or panics the saved pages are copied back on drop. The object is held like `UfoSlice` holds
+/// it, so it cannot be freed or reset mid transaction.
+pub struct TxnSlice<'a, T> {
+    _slot: RwLockReadGuard<'a, Option<WrappedUfoObject>>,
+    body: *mut u8,
+    len: usize,
+    // offset of the body from the start of its first page
+    page_offset: usize,
+    body_size: usize,
+    // page index to the page's original bytes, clipped to the body
+    undo: HashMap<usize, Vec<u8>>,
+    committed: bool,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T> TxnSlice<'a, T>
+where
+    T: Pod,
+{
+    fn new(handle: &'a UfoHandle) -> Result<Self, UfoErr> {
+        let info = handle.info();
+        handle.check_element::<T>()?;
+        handle.writable()?;
+
+        let (slot, body) = handle.locked_body::<u8>()?;
+        Ok(TxnSlice {
+            _slot: slot,
+            body,
+            len: info.element_ct,
+            page_offset: body as usize % system_page_size(),
+            body_size: info.body_size(),
+            undo: HashMap::new(),
+            committed: false,
+            _element: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> T {
+        assert!(idx < self.len, "index {} out of bounds", idx);
+        unsafe { self.body.cast::<T>().add(idx).read() }
+    }
+
+    pub fn set(&mut self, idx: usize, value: T) {
+        assert!(idx < self.len, "index {} out of bounds", idx);
+        let page_size = system_page_size();
+        let first = (self.page_offset + idx * size_of::<T>()) / page_size;
+        let last = (self.page_offset + (idx + 1) * size_of::<T>() - 1) / page_size;
+        for page in first..=last {
+            self.save(page, page_size);
+        }
+        unsafe { self.body.cast::<T>().add(idx).write(value) };
+    }
+
+    fn save(&mut self, page: usize, page_size: usize) {
+        let (body, page_offset, body_size) = (self.body, self.page_offset, self.body_size);
+        self.undo.entry(page).or_insert_with(|| {
+            let (start, end) = Self::page_bounds(page, page_size, page_offset, body_size);
+            unsafe { std::slice::from_raw_parts(body.add(start), end - start) }.to_vec()
+        });
+    }
+}
+
+impl<'a, T> TxnSlice<'a, T> {
+    // Byte range of the body covered by `page`
+    fn page_bounds(
+        page: usize,
+        page_size: usize,
+        page_offset: usize,
+        body_size: usize,
+    ) -> (usize, usize) {
+        let start = (page * page_size).saturating_sub(page_offset);
+        let end = ((page + 1) * page_size - page_offset).min(body_size);
+        (start, end)
+    }
+
+    fn rollback(&mut self) {
+        let page_size = system_page_size();
+        for (page, original) in self.undo.drain() {
+            let (start, _) = Self::page_bounds(page, page_size, self.page_offset, self.body_size);
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    original.as_ptr(),
+                    self.body.add(start),
+                    original.len(),
+                )
+            };
+        }
+    }
+}
+
+impl<'a, T> Drop for TxnSlice<'a, T> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.rollback();
+        }
+    }
+}
+
+impl UfoHandle {
+    /// Runs `f` against the body and undoes every write it made if it fails or panics
+    ///
+    /// Only pages `f` writes to are saved. Other threads see writes as they happen, this only
+    /// makes a failed update leave no trace. Read only and sealed objects are refused.
+    pub fn transaction<T, R, E, F>(&self, f: F) -> Result<R, E>
+    where
+        T: Pod,
+        E: From<UfoErr>,
+        F: FnOnce(&mut TxnSlice<'_, T>) -> Result<R, E>,
+    {
+        let mut txn = TxnSlice::new(self)?;
+        let result = f(&mut txn);
+        txn.committed = result.is_ok();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ufo_core::UfoInternalErr;
+
+    use crate::{
+        test_util::{sequence_params, test_core},
+        UfoErr,
+    };
+
+    #[test]
+    fn commit_keeps_writes() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(100 * 1000))?;
+
+        o.transaction(|txn| {
+            txn.set(1, 10);
+            txn.set(50 * 1000, 20);
+            Ok::<_, UfoErr>(())
+        })?;
+        o.transaction(|txn: &mut super::TxnSlice<u32>| {
+            assert_eq!(10, txn.get(1));
+            assert_eq!(20, txn.get(50 * 1000));
+            assert_eq!(2, txn.get(2));
+            Ok::<_, UfoErr>(())
+        })
+    }
+
+    #[test]
+    fn failure_rolls_back() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(100 * 1000))?;
+
+        let failed: Result<(), UfoErr> = o.transaction(|txn| {
+            txn.set(1, 10);
+            txn.set(99 * 1000, 20);
+            Err(UfoErr::Sealed)
+        });
+        assert!(failed.is_err());
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            o.transaction(|txn| -> Result<(), UfoErr> {
+                txn.set(3, 30u32);
+                panic!("midway")
+            })
+        }));
+        assert!(panicked.is_err());
+
+        let arr = unsafe { std::slice::from_raw_parts(o.body_ptr()?.cast::<u32>(), 100 * 1000) };
+        assert_eq!(1, arr[1]);
+        assert_eq!(3, arr[3]);
+        assert_eq!(99 * 1000, arr[99 * 1000]);
+
+        let wide: Result<(), UfoErr> = o.transaction(|_: &mut super::TxnSlice<u64>| Ok(()));
+        assert!(matches!(
+            wide,
+            Err(UfoErr::ElementSize {
+                stride: 4,
+                element_size: 8
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn reset_waits_for_the_transaction() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(100 * 1000))?;
+        let (reset_tx, reset_rx) = crossbeam::channel::bounded(1);
+
+        std::thread::scope(|s| {
+            let resetting = o.transaction(|txn| {
+                txn.set(1, 10u32);
+                let resetting = s.spawn(|| {
+                    let result = o.reset();
+                    let _ = reset_tx.send(());
+                    result
+                });
+                // the reset cannot get in while the transaction holds the object
+                assert!(reset_rx.recv_timeout(Duration::from_millis(50)).is_err());
+                assert_eq!(10, txn.get(1));
+                Ok::<_, UfoErr>(resetting)
+            })?;
+            resetting.join().unwrap()?;
+            Ok::<_, UfoErr>(())
+        })?;
+        assert_eq!(1, o.as_slice::<u32>()?[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn free_is_refused_during_the_transaction() -> Result<(), UfoErr> {
+        let core = test_core();
+        let o = core.new_ufo(sequence_params(100 * 1000))?;
+
+        o.transaction(|txn| {
+            txn.set(1, 10u32);
+            // from this thread as well as any other, waiting could never finish here
+            assert!(matches!(o.free(), Err(UfoErr::InUse)));
+            std::thread::scope(|s| {
+                assert!(matches!(s.spawn(|| o.free()).join().unwrap(), Err(UfoErr::InUse)));
+            });
+            Ok::<_, UfoErr>(())
+        })?;
+        assert_eq!(10, o.as_slice::<u32>()?[1]);
+        o.free()?;
+        assert!(matches!(o.body_ptr(), Err(UfoInternalErr::UfoNotFound)));
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fmt, time::Duration};
+
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender};
+
+/// Completion of an operation running in the background
+#[must_use = "dropping a waiter does not cancel the operation, call wait to observe its result"]
+pub struct UfoWaiter<T> {
+    result: Receiver<T>,
+}
+
+impl<T> UfoWaiter<T> {
+    pub(crate) fn new() -> (Sender<T>, Self) {
+        let (tx, rx) = channel::bounded(1);
+        (tx, UfoWaiter { result: rx })
+    }
+
+    /// Blocks until the operation completes
+    ///
+    /// Panics if the thread running the operation panicked instead of completing it.
+    pub fn wait(self) -> T {
+        self.result
+            .recv()
+            .expect("background ufo operation panicked")
+    }
+
+    /// Whether the operation has completed, so that `wait` will not block
+    ///
+    /// Stays false if the operation panicked, `wait` reports that.
+    pub fn is_done(&self) -> bool {
+        !self.result.is_empty()
+    }
+
+    /// Like `wait` but gives up after `timeout`
+    ///
+    /// The operation keeps running after a timeout, the error hands the waiter back so it can
+    /// be waited on again.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<T, WaitTimeout<T>> {
+        match self.result.recv_timeout(timeout) {
+            Ok(result) => Ok(result),
+            Err(RecvTimeoutError::Timeout) => Err(WaitTimeout { waiter: self }),
+            Err(RecvTimeoutError::Disconnected) => panic!("background ufo operation panicked"),
+        }
+    }
+}
+
+impl UfoWaiter<()> {
+    // Runs a blocking wait on its own thread so it can be waited on with a timeout
+    pub(crate) fn in_background<F>(wait: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let (done, waiter) = UfoWaiter::new();
+        std::thread::spawn(move || {
+            wait();
+            let _ = done.send(());
+        });
+        waiter
+    }
+}
+
+/// An operation did not complete in time, it is still pending
+pub struct WaitTimeout<T> {
+    waiter: UfoWaiter<T>,
+}
+
+impl<T> WaitTimeout<T> {
+    pub fn into_waiter(self) -> UfoWaiter<T> {
+        self.waiter
+    }
+}
+
+impl<T> fmt::Debug for WaitTimeout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitTimeout").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for WaitTimeout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out waiting for a ufo operation")
+    }
+}
+
+impl<T> std::error::Error for WaitTimeout<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_hands_the_waiter_back() {
+        let (tx, waiter) = UfoWaiter::new();
+        let pending = waiter
+            .wait_timeout(Duration::from_millis(10))
+            .unwrap_err();
+
+        tx.send(7).unwrap();
+        assert_eq!(7, pending.into_waiter().wait());
+    }
+}
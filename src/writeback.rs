@@ -0,0 +1,108 @@
+use ufo_core::{UfoObjectParams, UfoWriteListenerEvent};
+
+use crate::clamp_chunk;
+
+/// What a listener installed with `WithWritebackListener` is told about
+#[derive(Debug)]
+pub enum WritebackEvent<'a> {
+    /// Elements `start_elem..end_elem` are being written back, `data` holds exactly their bytes
+    ///
+    /// `data` is only valid for the duration of the call, copy it to keep it.
+    Writeback {
+        start_elem: usize,
+        end_elem: usize,
+        data: &'a [u8],
+    },
+    /// The object was reset, everything written back so far is stale
+    Reset,
+    /// The object was freed, no further events follow
+    Freed,
+}
+
+/// Installs a writeback listener that receives `WritebackEvent`s instead of raw pointers
+pub trait WithWritebackListener {
+    fn with_writeback_listener<F>(self, listener: F) -> Self
+    where
+        F: Fn(WritebackEvent<'_>) + Send + Sync + 'static;
+}
+
+impl WithWritebackListener for UfoObjectParams {
+    fn with_writeback_listener<F>(self, listener: F) -> Self
+    where
+        F: Fn(WritebackEvent<'_>) + Send + Sync + 'static,
+    {
+        let (stride, element_ct) = (self.stride, self.element_ct);
+        UfoObjectParams {
+            writeback_listener: Some(Box::new(move |event| match event {
+                UfoWriteListenerEvent::Writeback {
+                    start_idx,
+                    end_idx,
+                    data,
+                } => {
+                    let (end_idx, len) = match clamp_chunk(start_idx, end_idx, element_ct, stride) {
+                        Some(chunk) => chunk,
+                        None => return,
+                    };
+                    let data = unsafe { std::slice::from_raw_parts(data, len) };
+                    listener(WritebackEvent::Writeback {
+                        start_elem: start_idx,
+                        end_elem: end_idx,
+                        data,
+                    })
+                }
+                UfoWriteListenerEvent::Reset => listener(WritebackEvent::Reset),
+                UfoWriteListenerEvent::UfoWBDestroy => listener(WritebackEvent::Freed),
+            })),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        mem::size_of,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn events_are_typed_and_clipped() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let params = UfoObjectParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: Some(8),
+            read_only: false,
+            element_ct: 10,
+            populate: Box::new(|_, _, _| Ok(())),
+            writeback_listener: None,
+        }
+        .with_writeback_listener({
+            let seen = seen.clone();
+            move |event| {
+                let event = match event {
+                    WritebackEvent::Writeback {
+                        start_elem,
+                        end_elem,
+                        data,
+                    } => format!("{}..{} {}", start_elem, end_elem, data.len()),
+                    other => format!("{:?}", other),
+                };
+                seen.lock().unwrap().push(event);
+            }
+        });
+
+        let data = [0u8; 8 * 4];
+        let listener = params.writeback_listener.unwrap();
+        listener(UfoWriteListenerEvent::Writeback {
+            start_idx: 8,
+            end_idx: 16,
+            data: data.as_ptr(),
+        });
+        listener(UfoWriteListenerEvent::Reset);
+        listener(UfoWriteListenerEvent::UfoWBDestroy);
+
+        assert_eq!(vec!["8..10 8", "Reset", "Freed"], *seen.lock().unwrap());
+    }
+}
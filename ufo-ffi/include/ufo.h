@@ -0,0 +1,139 @@
+#ifndef UFO_H
+#define UFO_H
+
+/* Generated by cbindgen from src/lib.rs, do not edit by hand */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum UfoStatus {
+  UFO_STATUS_OK = 0,
+  /**
+   * A required pointer argument was null
+   */
+  UFO_STATUS_NULL_POINTER,
+  /**
+   * The writeback path was not valid UTF-8
+   */
+  UFO_STATUS_INVALID_ARGUMENT,
+  /**
+   * The core could not be started
+   */
+  UFO_STATUS_START_FAILED,
+  /**
+   * The core refused to allocate the object
+   */
+  UFO_STATUS_ALLOCATE_FAILED,
+  /**
+   * The core reported an error for an existing object
+   */
+  UFO_STATUS_CORE_ERROR,
+  /**
+   * `ufo_core_shutdown` was called while objects were still alive
+   */
+  UFO_STATUS_HANDLES_OUTSTANDING,
+  /**
+   * A bug in this library, the call was abandoned
+   */
+  UFO_STATUS_PANICKED,
+} UfoStatus;
+
+typedef enum UfoWritebackKind {
+  /**
+   * Elements `[start, end)` are being written back, `data` holds exactly their bytes
+   */
+  UFO_WRITEBACK_KIND_WRITEBACK = 0,
+  /**
+   * The object was reset, everything written back so far is stale
+   */
+  UFO_WRITEBACK_KIND_RESET,
+  /**
+   * The object was freed, no further calls follow
+   */
+  UFO_WRITEBACK_KIND_FREED,
+} UfoWritebackKind;
+
+typedef struct UfoCore UfoCore;
+
+typedef struct UfoHandle UfoHandle;
+
+/**
+ * Fills elements `[start, end)` into `fill`, returns 0 on success
+ *
+ * Called from the core's threads, possibly several at once for the same object.
+ */
+typedef int32_t (*UfoPopulateCallback)(void *userdata, size_t start, size_t end, uint8_t *fill);
+
+/**
+ * Told about writebacks, `start`, `end` and `data` are only meaningful for `Writeback`
+ *
+ * `data` is only valid for the duration of the call. Called from the core's threads.
+ */
+typedef void (*UfoWritebackCallback)(void *userdata,
+                                     UfoWritebackKind kind,
+                                     size_t start,
+                                     size_t end,
+                                     const uint8_t *data);
+
+/**
+ * Parameters for `ufo_new`, mirroring `UfoObjectParams`
+ */
+typedef struct UfoParams {
+  size_t header_size;
+  size_t stride;
+  /**
+   * 0 lets the core choose
+   */
+  size_t min_load_ct;
+  bool read_only;
+  size_t element_ct;
+  UfoPopulateCallback populate;
+  /**
+   * May be null for objects nobody needs to hear back from
+   */
+  UfoWritebackCallback writeback;
+  /**
+   * Passed to every callback, must stay valid until the object is freed
+   */
+  void *userdata;
+} UfoParams;
+
+/**
+ * Starts a core, on success `*out` owns it until `ufo_core_shutdown`
+ */
+UfoStatus ufo_core_new(const char *writeback_temp_path,
+                       size_t high_watermark,
+                       size_t low_watermark,
+                       UfoCore **out);
+
+/**
+ * Shuts the core down and releases it
+ *
+ * Returns `HandlesOutstanding` while objects are alive. On any error the core is still owned by
+ * the caller and can be shut down again, for instance once the objects are freed.
+ */
+UfoStatus ufo_core_shutdown(UfoCore *core);
+
+/**
+ * Allocates an object, on success `*out` owns it until `ufo_free`
+ *
+ * A populate callback returning nonzero zero-fills its range instead.
+ */
+UfoStatus ufo_new(const UfoCore *core, const UfoParams *params, UfoHandle **out);
+
+UfoStatus ufo_header_ptr(const UfoHandle *ufo, void **out);
+
+UfoStatus ufo_body_ptr(const UfoHandle *ufo, void **out);
+
+/**
+ * Discards the object's contents, they are populated again on next access
+ */
+UfoStatus ufo_reset(const UfoHandle *ufo);
+
+/**
+ * Frees the object and releases the handle, which is invalid afterwards even on error
+ */
+UfoStatus ufo_free(UfoHandle *ufo);
+
+#endif /* UFO_H */
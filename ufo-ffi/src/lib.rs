@@ -0,0 +1,375 @@
+//! C interface to the wrapper, see `include/ufo.h`
+//!
+//! Cores and objects are handed out as opaque pointers owned by the caller. `ufo_free` and
+//! `ufo_core_shutdown` release them, every other function only borrows. No function unwinds
+//! into the caller, failures are reported as a `UfoStatus`.
+//!
+//! Every pointer argument must be null or valid for its type, that is the only safety
+//! requirement of these functions.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+};
+
+use ufo_core::{UfoCoreConfig, UfoObjectParams, UfoPopulateError};
+
+use ufo::{UfoCore, UfoErr, UfoHandle, WithWritebackListener, WritebackEvent};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UfoStatus {
+    Ok = 0,
+    /// A required pointer argument was null
+    NullPointer,
+    /// The writeback path was not valid UTF-8
+    InvalidArgument,
+    /// The core could not be started
+    StartFailed,
+    /// The core refused to allocate the object
+    AllocateFailed,
+    /// The core reported an error for an existing object
+    CoreError,
+    /// `ufo_core_shutdown` was called while objects were still alive
+    HandlesOutstanding,
+    /// A bug in this library, the call was abandoned
+    Panicked,
+}
+
+/// Fills elements `[start, end)` into `fill`, returns 0 on success
+///
+/// Called from the core's threads, possibly several at once for the same object.
+pub type UfoPopulateCallback =
+    extern "C" fn(userdata: *mut c_void, start: usize, end: usize, fill: *mut u8) -> i32;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UfoWritebackKind {
+    /// Elements `[start, end)` are being written back, `data` holds exactly their bytes
+    Writeback = 0,
+    /// The object was reset, everything written back so far is stale
+    Reset,
+    /// The object was freed, no further calls follow
+    Freed,
+}
+
+/// Told about writebacks, `start`, `end` and `data` are only meaningful for `Writeback`
+///
+/// `data` is only valid for the duration of the call. Called from the core's threads.
+pub type UfoWritebackCallback = extern "C" fn(
+    userdata: *mut c_void,
+    kind: UfoWritebackKind,
+    start: usize,
+    end: usize,
+    data: *const u8,
+);
+
+/// Parameters for `ufo_new`, mirroring `UfoObjectParams`
+#[repr(C)]
+pub struct UfoParams {
+    pub header_size: usize,
+    pub stride: usize,
+    /// 0 lets the core choose
+    pub min_load_ct: usize,
+    pub read_only: bool,
+    pub element_ct: usize,
+    pub populate: Option<UfoPopulateCallback>,
+    /// May be null for objects nobody needs to hear back from
+    pub writeback: Option<UfoWritebackCallback>,
+    /// Passed to every callback, must stay valid until the object is freed
+    pub userdata: *mut c_void,
+}
+
+// the caller promises userdata may be used from the core's threads
+struct Userdata(*mut c_void);
+unsafe impl Send for Userdata {}
+unsafe impl Sync for Userdata {}
+
+fn guard<F>(f: F) -> UfoStatus
+where
+    F: FnOnce() -> UfoStatus,
+{
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(UfoStatus::Panicked)
+}
+
+/// Starts a core, on success `*out` owns it until `ufo_core_shutdown`
+#[no_mangle]
+pub unsafe extern "C" fn ufo_core_new(
+    writeback_temp_path: *const c_char,
+    high_watermark: usize,
+    low_watermark: usize,
+    out: *mut *mut UfoCore,
+) -> UfoStatus {
+    guard(|| {
+        if writeback_temp_path.is_null() || out.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        let path = match CStr::from_ptr(writeback_temp_path).to_str() {
+            Ok(path) => path.to_string(),
+            Err(_) => return UfoStatus::InvalidArgument,
+        };
+        let config = UfoCoreConfig {
+            writeback_temp_path: path,
+            high_watermark,
+            low_watermark,
+        };
+        match UfoCore::new_ufo_core(config) {
+            Ok(core) => {
+                *out = Box::into_raw(Box::new(core));
+                UfoStatus::Ok
+            }
+            Err(_) => UfoStatus::StartFailed,
+        }
+    })
+}
+
+/// Shuts the core down and releases it
+///
+/// Returns `HandlesOutstanding` while objects are alive. On any error the core is still owned by
+/// the caller and can be shut down again, for instance once the objects are freed.
+#[no_mangle]
+pub unsafe extern "C" fn ufo_core_shutdown(core: *mut UfoCore) -> UfoStatus {
+    guard(|| {
+        if core.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        // shut down through a clone, the caller's core is only released once that succeeded
+        match (*core).clone().shutdown() {
+            Ok(()) => {
+                drop(Box::from_raw(core));
+                UfoStatus::Ok
+            }
+            Err(UfoErr::HandlesOutstanding(_)) => UfoStatus::HandlesOutstanding,
+            Err(_) => UfoStatus::CoreError,
+        }
+    })
+}
+
+// Wraps the C callbacks, None if populate is missing
+fn object_params(params: &UfoParams) -> Option<UfoObjectParams> {
+    let populate = params.populate?;
+    let userdata = Arc::new(Userdata(params.userdata));
+
+    let mut prototype = UfoObjectParams {
+        header_size: params.header_size,
+        stride: params.stride,
+        min_load_ct: Some(params.min_load_ct).filter(|&ct| ct > 0),
+        read_only: params.read_only,
+        element_ct: params.element_ct,
+        populate: Box::new({
+            let userdata = userdata.clone();
+            move |start, end, fill| match populate(userdata.0, start, end, fill) {
+                0 => Ok(()),
+                _ => Err(UfoPopulateError),
+            }
+        }),
+        writeback_listener: None,
+    };
+    if let Some(writeback) = params.writeback {
+        prototype = prototype.with_writeback_listener(move |event| {
+            let (kind, start, end, data) = match event {
+                WritebackEvent::Writeback {
+                    start_elem,
+                    end_elem,
+                    data,
+                } => (
+                    UfoWritebackKind::Writeback,
+                    start_elem,
+                    end_elem,
+                    data.as_ptr(),
+                ),
+                WritebackEvent::Reset => (UfoWritebackKind::Reset, 0, 0, ptr::null()),
+                WritebackEvent::Freed => (UfoWritebackKind::Freed, 0, 0, ptr::null()),
+            };
+            writeback(userdata.0, kind, start, end, data)
+        });
+    }
+    Some(prototype)
+}
+
+/// Allocates an object, on success `*out` owns it until `ufo_free`
+///
+/// A populate callback returning nonzero zero-fills its range instead.
+#[no_mangle]
+pub unsafe extern "C" fn ufo_new(
+    core: *const UfoCore,
+    params: *const UfoParams,
+    out: *mut *mut UfoHandle,
+) -> UfoStatus {
+    guard(|| {
+        if core.is_null() || params.is_null() || out.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        let prototype = match object_params(&*params) {
+            Some(prototype) => prototype,
+            None => return UfoStatus::NullPointer,
+        };
+        match (*core).new_ufo(prototype) {
+            Ok(handle) => {
+                *out = Box::into_raw(Box::new(handle));
+                UfoStatus::Ok
+            }
+            Err(_) => UfoStatus::AllocateFailed,
+        }
+    })
+}
+
+unsafe fn object_ptr<F>(ufo: *const UfoHandle, out: *mut *mut c_void, get: F) -> UfoStatus
+where
+    F: FnOnce(&UfoHandle) -> Result<*mut c_void, ufo_core::UfoInternalErr>,
+{
+    guard(|| {
+        if ufo.is_null() || out.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        match get(&*ufo) {
+            Ok(p) => {
+                *out = p;
+                UfoStatus::Ok
+            }
+            Err(_) => {
+                *out = ptr::null_mut();
+                UfoStatus::CoreError
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ufo_header_ptr(ufo: *const UfoHandle, out: *mut *mut c_void) -> UfoStatus {
+    object_ptr(ufo, out, UfoHandle::header_ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ufo_body_ptr(ufo: *const UfoHandle, out: *mut *mut c_void) -> UfoStatus {
+    object_ptr(ufo, out, UfoHandle::body_ptr)
+}
+
+/// Discards the object's contents, they are populated again on next access
+#[no_mangle]
+pub unsafe extern "C" fn ufo_reset(ufo: *const UfoHandle) -> UfoStatus {
+    guard(|| {
+        if ufo.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        match (*ufo).reset() {
+            Ok(()) => UfoStatus::Ok,
+            Err(_) => UfoStatus::CoreError,
+        }
+    })
+}
+
+/// Frees the object and releases the handle, which is invalid afterwards even on error
+#[no_mangle]
+pub unsafe extern "C" fn ufo_free(ufo: *mut UfoHandle) -> UfoStatus {
+    guard(|| {
+        if ufo.is_null() {
+            return UfoStatus::NullPointer;
+        }
+        match Box::from_raw(ufo).free() {
+            Ok(()) => UfoStatus::Ok,
+            Err(_) => UfoStatus::CoreError,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{mem::size_of, sync::Mutex};
+    use ufo_core::UfoWriteListenerEvent;
+
+    extern "C" fn sequence(_: *mut c_void, start: usize, end: usize, fill: *mut u8) -> i32 {
+        let fill = fill.cast::<u32>();
+        for idx in start..end {
+            unsafe { fill.add(idx - start).write(idx as u32) };
+        }
+        0
+    }
+
+    type Seen = Mutex<Vec<(UfoWritebackKind, usize, usize)>>;
+
+    extern "C" fn record(
+        userdata: *mut c_void,
+        kind: UfoWritebackKind,
+        start: usize,
+        end: usize,
+        _: *const u8,
+    ) {
+        let seen = unsafe { &*userdata.cast::<Seen>() };
+        seen.lock().unwrap().push((kind, start, end));
+    }
+
+    fn params(writeback: Option<UfoWritebackCallback>, userdata: *mut c_void) -> UfoParams {
+        UfoParams {
+            header_size: 0,
+            stride: size_of::<u32>(),
+            min_load_ct: 4096,
+            read_only: false,
+            element_ct: 1000 * 1000,
+            populate: Some(sequence),
+            writeback,
+            userdata,
+        }
+    }
+
+    #[test]
+    fn it_works_through_ffi() {
+        unsafe {
+            let mut core = ptr::null_mut();
+            let path = b"/tmp\0".as_ptr().cast();
+            let status = ufo_core_new(path, 1024 * 1024 * 1024, 512 * 1024 * 1024, &mut core);
+            assert_eq!(UfoStatus::Ok, status);
+
+            let params = params(None, ptr::null_mut());
+            let mut ufo = ptr::null_mut();
+            assert_eq!(UfoStatus::Ok, ufo_new(core, &params, &mut ufo));
+
+            let mut body = ptr::null_mut();
+            assert_eq!(UfoStatus::Ok, ufo_body_ptr(ufo, &mut body));
+            let arr = std::slice::from_raw_parts(body.cast::<u32>(), 1000 * 1000);
+            for (i, x) in arr.iter().enumerate() {
+                assert_eq!(i as u32, *x);
+            }
+
+            assert_eq!(UfoStatus::HandlesOutstanding, ufo_core_shutdown(core));
+            assert_eq!(UfoStatus::Ok, ufo_free(ufo));
+            assert_eq!(UfoStatus::Ok, ufo_core_shutdown(core));
+        }
+    }
+
+    #[test]
+    fn writeback_reaches_c() {
+        let seen: Seen = Mutex::new(Vec::new());
+        let userdata = (&seen as *const Seen).cast_mut().cast();
+        let object = object_params(&params(Some(record), userdata)).unwrap();
+
+        let data = [0u8; 4096 * 4];
+        let listener = object.writeback_listener.unwrap();
+        listener(UfoWriteListenerEvent::Writeback {
+            start_idx: 4096,
+            end_idx: 8192,
+            data: data.as_ptr(),
+        });
+        listener(UfoWriteListenerEvent::Reset);
+        listener(UfoWriteListenerEvent::UfoWBDestroy);
+
+        assert_eq!(
+            vec![
+                (UfoWritebackKind::Writeback, 4096, 8192),
+                (UfoWritebackKind::Reset, 0, 0),
+                (UfoWritebackKind::Freed, 0, 0),
+            ],
+            *seen.lock().unwrap()
+        );
+        assert!(object_params(&UfoParams {
+            populate: None,
+            ..params(None, ptr::null_mut())
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,64 @@
+/*
+ * Drives the it_works sequence through the C interface, from the ufo-ffi directory:
+ *
+ *   cargo build -p ufo-ffi
+ *   cc -Iinclude tests/ffi_smoke.c ../target/debug/libufo_ffi.a -lpthread -ldl -lm -o ffi_smoke
+ *   ./ffi_smoke
+ */
+#include <stdint.h>
+#include <stdio.h>
+
+#include "ufo.h"
+
+#define ELEMENT_CT (1000 * 1000)
+
+static int32_t sequence(void *userdata, size_t start, size_t end, uint8_t *fill) {
+  uint32_t *out = (uint32_t *)fill;
+  (void)userdata;
+  for (size_t i = start; i < end; i++) {
+    out[i - start] = (uint32_t)i;
+  }
+  return 0;
+}
+
+#define CHECK(call)                                                          \
+  do {                                                                       \
+    UfoStatus status = (call);                                               \
+    if (status != UFO_STATUS_OK) {                                           \
+      fprintf(stderr, "%s failed with status %d\n", #call, (int)status);     \
+      return 1;                                                              \
+    }                                                                        \
+  } while (0)
+
+int main(void) {
+  UfoCore *core;
+  CHECK(ufo_core_new("/tmp", 1024 * 1024 * 1024, 512 * 1024 * 1024, &core));
+
+  UfoParams params = {
+      .header_size = 0,
+      .stride = sizeof(uint32_t),
+      .min_load_ct = 4096,
+      .read_only = false,
+      .element_ct = ELEMENT_CT,
+      .populate = sequence,
+      .writeback = NULL,
+      .userdata = NULL,
+  };
+  UfoHandle *ufo;
+  CHECK(ufo_new(core, &params, &ufo));
+
+  void *body;
+  CHECK(ufo_body_ptr(ufo, &body));
+  const uint32_t *arr = body;
+  for (size_t i = 0; i < ELEMENT_CT; i++) {
+    if (arr[i] != (uint32_t)i) {
+      fprintf(stderr, "element %zu is %u\n", i, arr[i]);
+      return 1;
+    }
+  }
+
+  CHECK(ufo_free(ufo));
+  CHECK(ufo_core_shutdown(core));
+  puts("ok");
+  return 0;
+}